@@ -0,0 +1,846 @@
+//! Instruction builders for off-chain callers.
+//!
+//! Each builder encodes a [`TransacInstruction`] and lists the accounts in the
+//! order the processor expects them. Only compiled with the `client` feature.
+
+use std::borrow::Cow;
+
+use solana_program::{
+    ed25519_program,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use crate::{
+    derive_record_address,
+    instruction::{CreateBatchEntry, CreateOptions, DelegateGrant, Opcode, TransacInstruction},
+    crypto::derive_password_key,
+    sigverify::{ED25519_OFFSETS_LEN, ED25519_OFFSETS_START, ETH_ADDRESS_LEN},
+    ACCESS_KEY_LEN, RECOVERY_SECRET_LEN, SALT_LEN,
+};
+
+/// Creates `owner`'s record named `label` at its derived address, storing the
+/// data held in `source_account`. `entropy` seeds the record's salt and
+/// should come from a proper random source.
+pub fn create_signature_ix(
+    program_id: &Pubkey,
+    source_account: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    password: &[u8],
+    entropy: [u8; SALT_LEN],
+    options: CreateOptions,
+) -> Instruction {
+    let (record, _) = derive_record_address(program_id, owner, label.as_bytes());
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::Create {
+            entropy,
+            label: label.to_string(),
+            password: password.to_vec(),
+            options,
+        },
+        vec![
+            AccountMeta::new_readonly(*source_account, false),
+            AccountMeta::new(record, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// As [`create_signature_ix`], but carrying `signature` in the instruction
+/// itself instead of a source account.
+pub fn create_inline_ix(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    signature: &[u8],
+    password: &[u8],
+    entropy: [u8; SALT_LEN],
+    options: CreateOptions,
+) -> Instruction {
+    let (record, _) = derive_record_address(program_id, owner, label.as_bytes());
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::CreateInline {
+            entropy,
+            label: label.to_string(),
+            signature: signature.to_vec(),
+            password: password.to_vec(),
+            options,
+        },
+        vec![
+            AccountMeta::new(record, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Reads the signature stored under `label`; it comes back as return data
+/// (see [`signature_text`]). `nonce` is the record's current view nonce and
+/// `authorities` are the co-signers the record requires, if any.
+pub fn view_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    label: &str,
+    password: &[u8],
+    nonce: u64,
+    authorities: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*record, false)];
+    accounts.extend(authorities.iter().map(|authority| AccountMeta::new_readonly(*authority, true)));
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::View {
+            label: label.to_string(),
+            password: password.to_vec(),
+            nonce,
+        },
+        accounts,
+    )
+}
+
+pub fn update_password_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    current_password: &[u8],
+    new_password: &[u8],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::UpdatePassword {
+            current_password: current_password.to_vec(),
+            new_password: new_password.to_vec(),
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+pub fn close_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    password: &[u8],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::Close {
+            password: password.to_vec(),
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+pub fn append_signature_ix(
+    program_id: &Pubkey,
+    source_account: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    password: &[u8],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::AppendSignature {
+            label: label.to_string(),
+            password: password.to_vec(),
+        },
+        vec![
+            AccountMeta::new_readonly(*source_account, false),
+            AccountMeta::new(*record, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Checks the password only; return data is `[1]` on a match, `[0]` otherwise.
+pub fn verify_ix(program_id: &Pubkey, record: &Pubkey, password: &[u8]) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::Verify {
+            password: password.to_vec(),
+        },
+        vec![AccountMeta::new(*record, false)],
+    )
+}
+
+pub fn transfer_ownership_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    password: &[u8],
+    new_owner: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::TransferOwnership {
+            password: password.to_vec(),
+            new_owner: *new_owner,
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+/// Resets a forgotten password with the record's recovery secret.
+pub fn recover_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    recovery_secret: [u8; RECOVERY_SECRET_LEN],
+    new_password: &[u8],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::Recover {
+            recovery_secret,
+            new_password: new_password.to_vec(),
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+/// One record for [`create_batch_ix`], described as for [`create_signature_ix`].
+pub struct BatchRecord<'a> {
+    pub source_account: &'a Pubkey,
+    pub label: &'a str,
+    pub password: &'a [u8],
+    pub entropy: [u8; SALT_LEN],
+    pub options: CreateOptions,
+}
+
+/// Creates all of `records` for `owner` in one instruction, deriving each
+/// record address and laying out the accounts the entries refer to.
+pub fn create_batch_ix(program_id: &Pubkey, owner: &Pubkey, records: &[BatchRecord]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    let mut entries = Vec::with_capacity(records.len());
+    for record in records {
+        let (address, _) = derive_record_address(program_id, owner, record.label.as_bytes());
+        entries.push(CreateBatchEntry {
+            source_index: accounts.len() as u8,
+            record_index: accounts.len() as u8 + 1,
+            entropy: record.entropy,
+            label: record.label.to_string(),
+            password: record.password.to_vec(),
+            options: record.options.clone(),
+        });
+        accounts.push(AccountMeta::new_readonly(*record.source_account, false));
+        accounts.push(AccountMeta::new(address, false));
+    }
+
+    Instruction::new_with_borsh(*program_id, &TransacInstruction::CreateBatch { entries }, accounts)
+}
+
+/// Proves the signature stored under `label` is `signer`'s over `message`.
+/// Must come right after [`ed25519_precompile_ix`] for the same signature.
+pub fn verify_ed25519_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    password: &[u8],
+    message: &[u8],
+    signer: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::VerifyEd25519 {
+            label: label.to_string(),
+            password: password.to_vec(),
+            message: message.to_vec(),
+            signer: *signer,
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+/// Ed25519 precompile instruction checking one `signature` by `signer` over
+/// `message`, with all three held in the instruction itself.
+pub fn ed25519_precompile_ix(signer: &Pubkey, message: &[u8], signature: &[u8]) -> Instruction {
+    let pubkey_offset = ED25519_OFFSETS_START + ED25519_OFFSETS_LEN;
+    let signature_offset = pubkey_offset + 32;
+    let message_offset = signature_offset + signature.len();
+
+    let mut data = vec![1u8, 0];
+    for field in [
+        signature_offset as u16,
+        u16::MAX,
+        pubkey_offset as u16,
+        u16::MAX,
+        message_offset as u16,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Proves the signature stored under `label` recovers to `expected_address`
+/// over `message_hash`.
+pub fn verify_secp256k1_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    password: &[u8],
+    message_hash: [u8; 32],
+    expected_address: [u8; ETH_ADDRESS_LEN],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::VerifySecp256k1 {
+            label: label.to_string(),
+            password: password.to_vec(),
+            message_hash,
+            expected_address,
+        },
+        vec![AccountMeta::new(*record, false), AccountMeta::new_readonly(*owner, true)],
+    )
+}
+
+/// Grants or, with `None`, revokes delegated read access.
+pub fn set_delegate_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    password: &[u8],
+    delegate: Option<DelegateGrant>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::SetDelegate {
+            password: password.to_vec(),
+            delegate,
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+/// Reads the signature stored under `label` as the record's delegate, with
+/// any co-signing `authorities` the record requires.
+pub fn delegate_view_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    delegate: &Pubkey,
+    label: &str,
+    access_key: [u8; ACCESS_KEY_LEN],
+    nonce: u64,
+    authorities: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*record, false),
+        AccountMeta::new_readonly(*delegate, true),
+    ];
+    accounts.extend(authorities.iter().map(|authority| AccountMeta::new_readonly(*authority, true)));
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::DelegateView {
+            label: label.to_string(),
+            access_key,
+            nonce,
+        },
+        accounts,
+    )
+}
+
+/// Shows a signature returned by a view as text. Signatures are stored as
+/// opaque bytes, so anything that isn't UTF-8 is replaced rather than failing.
+pub fn signature_text(return_data: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(return_data)
+}
+
+/// Splits the return data of a create instruction into each new record's
+/// address and bump seed. A batch returns one pair per entry, in order.
+pub fn created_records(return_data: &[u8]) -> Vec<(Pubkey, u8)> {
+    return_data
+        .chunks_exact(33)
+        .map(|chunk| (Pubkey::new_from_array(chunk[..32].try_into().unwrap()), chunk[32]))
+        .collect()
+}
+
+/// Replaces the record's metadata; `owner` pays if the record has to grow.
+pub fn set_metadata_ix(program_id: &Pubkey, record: &Pubkey, owner: &Pubkey, metadata: &[u8]) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::SetMetadata {
+            metadata: metadata.to_vec(),
+        },
+        vec![
+            AccountMeta::new(*record, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Checks the password and, on a match, calls `callback_program` with `data`
+/// and `callback_accounts`.
+pub fn verify_and_call_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    password: &[u8],
+    callback_program: &Pubkey,
+    callback_accounts: &[AccountMeta],
+    data: &[u8],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*record, false),
+        AccountMeta::new_readonly(*callback_program, false),
+    ];
+    accounts.extend_from_slice(callback_accounts);
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::VerifyAndCall {
+            password: password.to_vec(),
+            data: data.to_vec(),
+        },
+        accounts,
+    )
+}
+
+/// Replaces the signature stored under `label` with the data held in
+/// `source_account`.
+pub fn rotate_signature_ix(
+    program_id: &Pubkey,
+    source_account: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    password: &[u8],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::RotateSignature {
+            label: label.to_string(),
+            password: password.to_vec(),
+        },
+        vec![
+            AccountMeta::new_readonly(*source_account, false),
+            AccountMeta::new(*record, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Deletes the signature stored under `label`.
+pub fn remove_signature_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    owner: &Pubkey,
+    label: &str,
+    password: &[u8],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::RemoveSignature {
+            label: label.to_string(),
+            password: password.to_vec(),
+        },
+        vec![AccountMeta::new(*record, false), AccountMeta::new_readonly(*owner, true)],
+    )
+}
+
+/// Pre-hashes `password` for the `*Prehashed` instructions, with the salt and
+/// iterations from the record's `password_params`. The result grants the
+/// same access as the password, so guard it as carefully.
+pub fn prehash_password(password: &[u8], salt: &[u8; SALT_LEN], iterations: u32) -> [u8; 32] {
+    *derive_password_key(password, salt, iterations)
+}
+
+/// Checks a pre-hashed password; return data is as for [`verify_ix`].
+pub fn verify_prehashed_ix(program_id: &Pubkey, record: &Pubkey, password_key: [u8; 32]) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::VerifyPrehashed { password_key },
+        vec![AccountMeta::new(*record, false)],
+    )
+}
+
+/// As [`view_ix`], with a pre-hashed password.
+pub fn view_prehashed_ix(
+    program_id: &Pubkey,
+    record: &Pubkey,
+    label: &str,
+    password_key: [u8; 32],
+    nonce: u64,
+    authorities: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*record, false)];
+    accounts.extend(authorities.iter().map(|authority| AccountMeta::new_readonly(*authority, true)));
+    Instruction::new_with_borsh(
+        *program_id,
+        &TransacInstruction::ViewPrehashed {
+            label: label.to_string(),
+            password_key,
+            nonce,
+        },
+        accounts,
+    )
+}
+
+/// One account an instruction expects, as listed in [`describe_accounts`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountSpec {
+    pub role: &'static str,
+    pub writable: bool,
+    pub signer: bool,
+    /// Stands for any number of accounts, possibly none, filling out the
+    /// rest of the list. Consecutive variadic specs repeat together, as a
+    /// group, in the order listed.
+    pub variadic: bool,
+    /// The caller picks the flags, and the program passes them on unchanged,
+    /// so `writable` and `signer` don't apply.
+    pub caller_flags: bool,
+}
+
+const fn account(role: &'static str, writable: bool, signer: bool) -> AccountSpec {
+    AccountSpec {
+        role,
+        writable,
+        signer,
+        variadic: false,
+        caller_flags: false,
+    }
+}
+
+const fn remaining(role: &'static str, writable: bool, signer: bool) -> AccountSpec {
+    AccountSpec {
+        role,
+        writable,
+        signer,
+        variadic: true,
+        caller_flags: false,
+    }
+}
+
+/// Any number of accounts, each with whatever flags the caller gives it.
+const fn forwarded(role: &'static str) -> AccountSpec {
+    AccountSpec {
+        role,
+        writable: false,
+        signer: false,
+        variadic: true,
+        caller_flags: true,
+    }
+}
+
+const RECORD: AccountSpec = account("Record account", true, false);
+const OWNER: AccountSpec = account("Record owner", false, true);
+const PAYING_OWNER: AccountSpec = account("Record owner, who pays for any extra rent", true, true);
+const SYSTEM_PROGRAM: AccountSpec = account("System program", false, false);
+const AUTHORITIES: AccountSpec = remaining("Authorities co-signing the view, if the record has any", false, true);
+
+const NEW_RECORD: AccountSpec = account("Record account, the PDA for (owner, label)", true, false);
+const CREATING_OWNER: AccountSpec = account("Owner, who also pays for the record", true, true);
+
+const CREATE_ACCOUNTS: &[AccountSpec] = &[
+    account("Source account whose data is the signature", false, false),
+    NEW_RECORD,
+    CREATING_OWNER,
+    SYSTEM_PROGRAM,
+];
+const CREATE_INLINE_ACCOUNTS: &[AccountSpec] = &[NEW_RECORD, CREATING_OWNER, SYSTEM_PROGRAM];
+const VIEW_ACCOUNTS: &[AccountSpec] = &[RECORD, AUTHORITIES];
+const OWNER_ACCOUNTS: &[AccountSpec] = &[RECORD, OWNER];
+const CLOSE_ACCOUNTS: &[AccountSpec] = &[
+    RECORD,
+    account("Destination for the reclaimed lamports", true, false),
+    OWNER,
+];
+const APPEND_ACCOUNTS: &[AccountSpec] = &[
+    account("Source account whose data is the signature", false, false),
+    RECORD,
+    PAYING_OWNER,
+    SYSTEM_PROGRAM,
+];
+const RECORD_ONLY_ACCOUNTS: &[AccountSpec] = &[RECORD];
+const CREATE_BATCH_ACCOUNTS: &[AccountSpec] = &[
+    account("Owner, who also pays for the records", true, true),
+    SYSTEM_PROGRAM,
+    remaining("Source account of an entry", false, false),
+    remaining("Record account of that entry, the PDA for (owner, label)", true, false),
+];
+const VERIFY_ED25519_ACCOUNTS: &[AccountSpec] = &[RECORD, account("Instructions sysvar", false, false), OWNER];
+const DELEGATE_VIEW_ACCOUNTS: &[AccountSpec] = &[RECORD, account("Delegate", false, true), AUTHORITIES];
+const SET_METADATA_ACCOUNTS: &[AccountSpec] = &[RECORD, PAYING_OWNER, SYSTEM_PROGRAM];
+const VERIFY_AND_CALL_ACCOUNTS: &[AccountSpec] = &[
+    RECORD,
+    account("Callback program", false, false),
+    forwarded("Accounts forwarded to the callback, with the flags it needs"),
+];
+const ROTATE_ACCOUNTS: &[AccountSpec] = &[
+    account("Source account whose data is the new signature", false, false),
+    RECORD,
+    PAYING_OWNER,
+    SYSTEM_PROGRAM,
+];
+
+/// Accounts the instruction with opcode `op` expects, in order, with their
+/// flags. Unknown opcodes get an empty list. Mirrors the "Accounts expected"
+/// lists on [`TransacInstruction`].
+pub fn describe_accounts(op: u8) -> &'static [AccountSpec] {
+    let Some(op) = Opcode::from_u8(op) else {
+        return &[];
+    };
+    match op {
+        Opcode::Create => CREATE_ACCOUNTS,
+        Opcode::View | Opcode::ViewPrehashed => VIEW_ACCOUNTS,
+        Opcode::UpdatePassword
+        | Opcode::TransferOwnership
+        | Opcode::Recover
+        | Opcode::VerifySecp256k1
+        | Opcode::SetDelegate
+        | Opcode::RemoveSignature => OWNER_ACCOUNTS,
+        Opcode::Close => CLOSE_ACCOUNTS,
+        Opcode::AppendSignature => APPEND_ACCOUNTS,
+        Opcode::Verify | Opcode::VerifyPrehashed => RECORD_ONLY_ACCOUNTS,
+        Opcode::CreateBatch => CREATE_BATCH_ACCOUNTS,
+        Opcode::VerifyEd25519 => VERIFY_ED25519_ACCOUNTS,
+        Opcode::DelegateView => DELEGATE_VIEW_ACCOUNTS,
+        Opcode::SetMetadata => SET_METADATA_ACCOUNTS,
+        Opcode::VerifyAndCall => VERIFY_AND_CALL_ACCOUNTS,
+        Opcode::RotateSignature => ROTATE_ACCOUNTS,
+        Opcode::CreateInline => CREATE_INLINE_ACCOUNTS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::program_error::ProgramError;
+
+    use super::*;
+    use crate::test_support::{MockAccount, MockOutcome, MockRuntime};
+
+    /// One instruction per opcode, as the builders lay them out.
+    fn sample_instructions(program_id: &Pubkey) -> Vec<Instruction> {
+        let (owner, record, source) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let authorities = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let password = b"correct horse 1".as_slice();
+        let grant = DelegateGrant {
+            delegate: Pubkey::new_unique(),
+            expires_at: 1,
+            access_key: [1; ACCESS_KEY_LEN],
+        };
+        let batch = [
+            BatchRecord {
+                source_account: &source,
+                label: "first",
+                password,
+                entropy: [1; SALT_LEN],
+                options: CreateOptions::default(),
+            },
+            BatchRecord {
+                source_account: &source,
+                label: "second",
+                password,
+                entropy: [2; SALT_LEN],
+                options: CreateOptions::default(),
+            },
+        ];
+        // Forwarded with whatever flags the callback needs.
+        let callback_accounts = [
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), true),
+        ];
+        vec![
+            create_signature_ix(program_id, &source, &owner, "main", password, [0; SALT_LEN], CreateOptions::default()),
+            view_ix(program_id, &record, "main", password, 0, &authorities),
+            update_password_ix(program_id, &record, &owner, password, b"battery staple 2"),
+            close_ix(program_id, &record, &Pubkey::new_unique(), &owner, password),
+            verify_ix(program_id, &record, password),
+            append_signature_ix(program_id, &source, &record, &owner, "second", password),
+            transfer_ownership_ix(program_id, &record, &owner, password, &Pubkey::new_unique()),
+            recover_ix(program_id, &record, &owner, [9; RECOVERY_SECRET_LEN], b"battery staple 2"),
+            create_batch_ix(program_id, &owner, &batch),
+            verify_ed25519_ix(program_id, &record, &owner, "main", password, b"message", &Pubkey::new_unique()),
+            verify_secp256k1_ix(program_id, &record, &owner, "main", password, [0; 32], [0; ETH_ADDRESS_LEN]),
+            set_delegate_ix(program_id, &record, &owner, password, Some(grant)),
+            delegate_view_ix(program_id, &record, &Pubkey::new_unique(), "main", [1; ACCESS_KEY_LEN], 0, &authorities),
+            set_metadata_ix(program_id, &record, &owner, b"note"),
+            verify_and_call_ix(program_id, &record, password, &Pubkey::new_unique(), &callback_accounts, b"data"),
+            rotate_signature_ix(program_id, &source, &record, &owner, "main", password),
+            verify_prehashed_ix(program_id, &record, [0; 32]),
+            view_prehashed_ix(program_id, &record, "main", [0; 32], 0, &authorities),
+            create_inline_ix(program_id, &owner, "main", b"sig", password, [0; SALT_LEN], CreateOptions::default()),
+            remove_signature_ix(program_id, &record, &owner, "main", password),
+        ]
+    }
+
+    fn fixed_len(specs: &[AccountSpec]) -> usize {
+        specs.iter().filter(|spec| !spec.variadic).count()
+    }
+
+    /// Whether `metas` has exactly the flags `specs` lists, repeating the
+    /// variadic group as often as needed.
+    fn conforms(metas: &[AccountMeta], specs: &[AccountSpec]) -> bool {
+        let fixed = fixed_len(specs);
+        let group = &specs[fixed..];
+        let flags_match = |meta: &AccountMeta, spec: &AccountSpec| {
+            spec.caller_flags || meta.is_writable == spec.writable && meta.is_signer == spec.signer
+        };
+        if metas.len() < fixed || (group.is_empty() && metas.len() != fixed) {
+            return false;
+        }
+        let rest = &metas[fixed..];
+        let fixed_match = metas.iter().zip(&specs[..fixed]).all(|(meta, spec)| flags_match(meta, spec));
+        let rest_match = group.is_empty()
+            || rest.len().is_multiple_of(group.len())
+                && rest.iter().zip(group.iter().cycle()).all(|(meta, spec)| flags_match(meta, spec));
+        fixed_match && rest_match
+    }
+
+    #[test]
+    fn builders_match_the_account_specs() {
+        let program_id = Pubkey::new_unique();
+        let instructions = sample_instructions(&program_id);
+        for (op, instruction) in instructions.iter().enumerate() {
+            assert_eq!(instruction.data[0] as usize, op);
+            let decoded = TransacInstruction::unpack(&instruction.data).unwrap();
+            assert_eq!(decoded.opcode(), Opcode::from_u8(op as u8).unwrap());
+            assert_eq!(decoded.opcode() as usize, op);
+            let specs = describe_accounts(op as u8);
+            assert!(conforms(&instruction.accounts, specs), "opcode {op}: {:?}", instruction.accounts);
+        }
+        assert_eq!(instructions.len(), TransacInstruction::LAST_OPCODE as usize + 1);
+        assert!(describe_accounts(TransacInstruction::LAST_OPCODE + 1).is_empty());
+    }
+
+    #[test]
+    fn processor_needs_every_fixed_account() {
+        let program_id = Pubkey::new_unique();
+        let runtime = MockRuntime::default();
+        for (op, instruction) in sample_instructions(&program_id).iter().enumerate() {
+            let fixed = fixed_len(describe_accounts(op as u8));
+            let mut accounts: Vec<_> = instruction.accounts[..fixed - 1]
+                .iter()
+                .map(|meta| {
+                    let mut account = MockAccount::new(meta.pubkey, system_program::id(), 0);
+                    account.is_signer = meta.is_signer;
+                    account.is_writable = meta.is_writable;
+                    account
+                })
+                .collect();
+            let outcome = runtime.process(&program_id, &mut accounts, &instruction.data);
+            assert_eq!(outcome.result, Err(ProgramError::NotEnoughAccountKeys), "opcode {op}");
+        }
+    }
+
+    /// Runs builder output against mock accounts kept by pubkey, as a bank
+    /// would, creating any account not seen before.
+    struct Bank {
+        runtime: MockRuntime,
+        accounts: Vec<MockAccount>,
+    }
+
+    impl Bank {
+        /// A bank holding `owner` and the system program.
+        fn new(owner: &MockAccount) -> Self {
+            let mut system = MockAccount::new(system_program::id(), Pubkey::default(), 1);
+            system.executable = true;
+            Bank {
+                runtime: MockRuntime::default(),
+                accounts: vec![owner.clone(), system],
+            }
+        }
+
+        fn send(&mut self, instruction: &Instruction) -> MockOutcome {
+            let mut accounts: Vec<_> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let mut account = self
+                        .accounts
+                        .iter()
+                        .find(|account| account.key == meta.pubkey)
+                        .cloned()
+                        .unwrap_or_else(|| MockAccount::new(meta.pubkey, system_program::id(), 0));
+                    account.is_signer = meta.is_signer;
+                    account.is_writable = meta.is_writable;
+                    account
+                })
+                .collect();
+            let outcome = self.runtime.process(&instruction.program_id, &mut accounts, &instruction.data);
+            for account in accounts {
+                self.accounts.retain(|kept| kept.key != account.key);
+                self.accounts.push(account);
+            }
+            outcome
+        }
+    }
+
+    #[test]
+    fn builders_drive_the_processor() {
+        let program_id = Pubkey::new_unique();
+        let owner = MockAccount::new(Pubkey::new_unique(), system_program::id(), 10_000_000_000);
+        let mut bank = Bank::new(&owner);
+        let (record, _) = derive_record_address(&program_id, &owner.key, b"main");
+        let (password, new_password) = (b"correct horse 1".as_slice(), b"battery staple 2".as_slice());
+
+        let options = CreateOptions::default();
+        let create = create_inline_ix(&program_id, &owner.key, "main", b"sig", password, [7; SALT_LEN], options);
+        bank.send(&create).result.unwrap();
+        assert_eq!(bank.send(&verify_ix(&program_id, &record, password)).return_data.unwrap(), [1]);
+        assert_eq!(bank.send(&view_ix(&program_id, &record, "main", password, 0, &[])).return_data.unwrap(), b"sig");
+
+        bank.send(&update_password_ix(&program_id, &record, &owner.key, password, new_password)).result.unwrap();
+        let view = view_ix(&program_id, &record, "main", new_password, 1, &[]);
+        assert_eq!(bank.send(&view).return_data.unwrap(), b"sig");
+    }
+
+    #[test]
+    fn invalid_utf8_signatures_are_stored_and_shown() {
+        let program_id = Pubkey::new_unique();
+        let owner = MockAccount::new(Pubkey::new_unique(), system_program::id(), 10_000_000_000);
+        let mut bank = Bank::new(&owner);
+        let (record, _) = derive_record_address(&program_id, &owner.key, b"main");
+        let password = b"correct horse 1".as_slice();
+        let signature = [b's', b'i', b'g', 0xff, 0xfe];
+
+        let options = CreateOptions::default();
+        let create = create_inline_ix(&program_id, &owner.key, "main", &signature, password, [7; SALT_LEN], options);
+        bank.send(&create).result.unwrap();
+        let returned = bank.send(&view_ix(&program_id, &record, "main", password, 0, &[])).return_data.unwrap();
+        assert_eq!(returned, signature);
+        assert_eq!(signature_text(&returned), "sig\u{fffd}\u{fffd}");
+        assert_eq!(signature_text(b"plain text"), "plain text");
+    }
+}
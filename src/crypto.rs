@@ -0,0 +1,366 @@
+//! Key derivation and signature encryption.
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use solana_program::{
+    alt_bn128::prelude::{alt_bn128_multiplication, ALT_BN128_POINT_SIZE},
+    hash::hashv,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{error::TransacError, ACCESS_KEY_LEN, RECOVERY_SECRET_LEN, SALT_LEN};
+
+/// Length of the per-entry ChaCha20-Poly1305 nonce.
+pub const NONCE_LEN: usize = 12;
+
+/// Length of the Poly1305 tag appended to every ciphertext.
+pub const TAG_LEN: usize = 16;
+
+/// Length of an uncompressed alt_bn128 G1 point, as recovery public keys and
+/// ephemeral keys are stored.
+pub const RECOVERY_POINT_LEN: usize = ALT_BN128_POINT_SIZE;
+
+/// Generator of the alt_bn128 G1 group, `(1, 2)` as big-endian coordinates.
+const G1_GENERATOR: [u8; RECOVERY_POINT_LEN] = {
+    let mut point = [0u8; RECOVERY_POINT_LEN];
+    point[31] = 1;
+    point[63] = 2;
+    point
+};
+
+/// Key signatures are encrypted under, or one wrapping it. Never stored.
+pub type EncryptionKey = Zeroizing<[u8; 32]>;
+
+/// What a secret expands to: the verifier kept in the record and the key that
+/// wraps the record's data key. Both come from the same input but under
+/// different domain tags, so the stored verifier reveals nothing about the
+/// wrapping key.
+pub struct SecretKeys {
+    pub verifier: [u8; 32],
+    pub wrapping_key: EncryptionKey,
+}
+
+pub fn derive_password_keys(password: &[u8], salt: &[u8; SALT_LEN], iterations: u32) -> SecretKeys {
+    expand_password_key(&derive_password_key(password, salt, iterations))
+}
+
+/// The PBKDF2 output everything else about a password is derived from. This
+/// is what a client sends when it pre-hashes the password.
+pub fn derive_password_key(password: &[u8], salt: &[u8; SALT_LEN], iterations: u32) -> EncryptionKey {
+    Zeroizing::new(pbkdf2_sha256(password, salt, iterations))
+}
+
+pub fn expand_password_key(password_key: &[u8; 32]) -> SecretKeys {
+    SecretKeys {
+        verifier: hashv(&[b"transac-verifier", password_key]).to_bytes(),
+        wrapping_key: Zeroizing::new(hashv(&[b"transac-wrapping", password_key]).to_bytes()),
+    }
+}
+
+/// Verifier kept for a recovery secret. Recovery secrets are full-entropy
+/// keys rather than passwords, so they go through a single hash instead of
+/// PBKDF2.
+pub fn derive_recovery_verifier(secret: &[u8; RECOVERY_SECRET_LEN]) -> [u8; 32] {
+    hashv(&[b"transac-recovery-verifier", secret]).to_bytes()
+}
+
+/// Public half of a recovery secret: `r·G` on alt_bn128, with the scalar `r`
+/// hashed from the secret. The program wraps fresh data keys to it, see
+/// [`derive_recovery_wrap`], which is what lets it re-key a record without
+/// the secret.
+pub fn derive_recovery_public_key(
+    secret: &[u8; RECOVERY_SECRET_LEN],
+) -> Result<[u8; RECOVERY_POINT_LEN], ProgramError> {
+    let scalar = hash_to_scalar(&[b"transac-recovery-scalar", secret]);
+    g1_multiply(&G1_GENERATOR, &scalar)
+}
+
+/// Picks an ephemeral key for wrapping a data key to `public_key`, ECIES
+/// style: returns `e·G`, to be stored, and a wrapping key hashed from the
+/// shared point `e·P`. The scalar `e` is hashed from the data key being
+/// wrapped, which is secret, and `nonce`, which must be fresh.
+pub fn derive_recovery_wrap(
+    public_key: &[u8; RECOVERY_POINT_LEN],
+    data_key: &EncryptionKey,
+    nonce: &[u8; NONCE_LEN],
+) -> Result<([u8; RECOVERY_POINT_LEN], EncryptionKey), ProgramError> {
+    let scalar = hash_to_scalar(&[b"transac-recovery-ephemeral", data_key.as_ref(), nonce]);
+    let ephemeral_key = g1_multiply(&G1_GENERATOR, &scalar)?;
+    let shared = g1_multiply(public_key, &scalar)?;
+    Ok((ephemeral_key, recovery_wrapping_key(&shared)))
+}
+
+/// The wrapping key [`derive_recovery_wrap`] produced alongside
+/// `ephemeral_key`, recomputed from the secret as `r·E`.
+pub fn derive_recovery_unwrap(
+    secret: &[u8; RECOVERY_SECRET_LEN],
+    ephemeral_key: &[u8; RECOVERY_POINT_LEN],
+) -> Result<EncryptionKey, ProgramError> {
+    let scalar = hash_to_scalar(&[b"transac-recovery-scalar", secret]);
+    let shared = g1_multiply(ephemeral_key, &scalar)?;
+    Ok(recovery_wrapping_key(&shared))
+}
+
+fn recovery_wrapping_key(shared: &[u8; RECOVERY_POINT_LEN]) -> EncryptionKey {
+    Zeroizing::new(hashv(&[b"transac-recovery-wrapping", shared]).to_bytes())
+}
+
+/// A big-endian alt_bn128 scalar from a hash. Clearing the top three bits
+/// keeps it below the group order, at a cost of under three bits of entropy.
+fn hash_to_scalar(parts: &[&[u8]]) -> Zeroizing<[u8; 32]> {
+    let mut scalar = Zeroizing::new(hashv(parts).to_bytes());
+    scalar[0] &= 0x1f;
+    scalar
+}
+
+/// `scalar·point`, through the `alt_bn128` syscall on-chain. A point that
+/// isn't on the curve can only come from forged data.
+fn g1_multiply(
+    point: &[u8; RECOVERY_POINT_LEN],
+    scalar: &[u8; 32],
+) -> Result<[u8; RECOVERY_POINT_LEN], ProgramError> {
+    let mut input = Zeroizing::new(Vec::with_capacity(RECOVERY_POINT_LEN + 32));
+    input.extend_from_slice(point);
+    input.extend_from_slice(scalar);
+    let product = alt_bn128_multiplication(&input).map_err(|_| TransacError::DecryptionFailed)?;
+    product
+        .try_into()
+        .map_err(|_| TransacError::DecryptionFailed.into())
+}
+
+/// Key a delegate's copy of the data key is wrapped under. No verifier is
+/// kept: a wrong access key simply fails to unwrap.
+pub fn derive_access_wrapping_key(access_key: &[u8; ACCESS_KEY_LEN]) -> EncryptionKey {
+    Zeroizing::new(hashv(&[b"transac-access-wrapping", access_key]).to_bytes())
+}
+
+/// Picks a record's data key. It only has to be unpredictable from the
+/// account contents, which the password's wrapping key already is, and
+/// `nonce` keeps each pick distinct.
+pub fn derive_data_key(wrapping_key: &EncryptionKey, nonce: &[u8; NONCE_LEN]) -> EncryptionKey {
+    Zeroizing::new(hashv(&[b"transac-data", wrapping_key.as_ref(), nonce]).to_bytes())
+}
+
+/// Encrypts `plaintext`, binding it to `associated_data` (the entry label, or
+/// which secret wraps a data key) so that ciphertexts can't be swapped.
+pub fn encrypt(
+    key: &EncryptionKey,
+    nonce: &[u8; NONCE_LEN],
+    associated_data: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, ProgramError> {
+    ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: plaintext,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Decrypts and authenticates `ciphertext`. A wrong key or tampered data
+/// fails the tag check rather than yielding garbage.
+pub fn decrypt(
+    key: &EncryptionKey,
+    nonce: &[u8; NONCE_LEN],
+    associated_data: &[u8],
+    ciphertext: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, ProgramError> {
+    ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: associated_data,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| TransacError::DecryptionFailed.into())
+}
+
+/// Mixes the caller's entropy with the record address so that two records
+/// created with the same entropy still end up with distinct salts.
+pub fn derive_salt(entropy: &[u8], record_key: &Pubkey) -> [u8; SALT_LEN] {
+    let digest = hashv(&[entropy, record_key.as_ref()]).to_bytes();
+
+    let mut salt = [0u8; SALT_LEN];
+    salt.copy_from_slice(&digest[..SALT_LEN]);
+    salt
+}
+
+/// One `sol_sha256` call over `parts`. Tests tally what each call is charged.
+pub(crate) fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    #[cfg(test)]
+    tests::charge_sha256(parts);
+    hashv(parts).to_bytes()
+}
+
+/// PBKDF2-HMAC-SHA256 with a single 32-byte output block.
+///
+/// Built on `hashv` so that on-chain every SHA256 goes through the
+/// `sol_sha256` syscall, which is far cheaper than hashing in program code.
+/// Each iteration is one HMAC, that is two `sol_sha256` calls, and nothing is
+/// allocated: the bump allocator never frees, so a per-call buffer would eat
+/// the heap at higher iteration counts.
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+
+    let mut key = [0u8; BLOCK_LEN];
+    if password.len() > BLOCK_LEN {
+        key[..32].copy_from_slice(&hashv(&[password]).to_bytes());
+    } else {
+        key[..password.len()].copy_from_slice(password);
+    }
+    let mut inner_pad = [0x36u8; BLOCK_LEN];
+    let mut outer_pad = [0x5cu8; BLOCK_LEN];
+    for i in 0..BLOCK_LEN {
+        inner_pad[i] ^= key[i];
+        outer_pad[i] ^= key[i];
+    }
+    key.zeroize();
+
+    let hmac = |message: &[u8], suffix: &[u8]| -> [u8; 32] {
+        let inner = sha256(&[&inner_pad, message, suffix]);
+        sha256(&[&outer_pad, &inner])
+    };
+
+    // U1 = HMAC(P, S || INT(1)), Ui = HMAC(P, Ui-1), T1 = U1 ^ ... ^ Uc
+    let mut u = hmac(salt, &1u32.to_be_bytes());
+    let mut output = u;
+    for _ in 1..iterations {
+        u = hmac(&u, &[]);
+        for (out, byte) in output.iter_mut().zip(u.iter()) {
+            *out ^= byte;
+        }
+    }
+
+    inner_pad.zeroize();
+    outer_pad.zeroize();
+    output
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::DEFAULT_PBKDF2_ITERATIONS;
+
+    thread_local! {
+        static SHA256_UNITS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    /// Adds what the runtime charges for `sol_sha256` over `parts` to this
+    /// thread's tally, using the default cost table of the 1.18 runtime: 85
+    /// units per call, plus the larger of 10 and half its length per slice.
+    pub(super) fn charge_sha256(parts: &[&[u8]]) {
+        let units = 85 + parts.iter().map(|part| (part.len() as u64 / 2).max(10)).sum::<u64>();
+        SHA256_UNITS.with(|tally| tally.set(tally.get() + units));
+    }
+
+    /// Syscall units charged to the `sol_sha256` calls that `run` makes.
+    pub(crate) fn sha256_units(run: impl FnOnce()) -> u64 {
+        SHA256_UNITS.with(|tally| tally.set(0));
+        run();
+        SHA256_UNITS.with(Cell::get)
+    }
+
+    /// Syscall units `pbkdf2_sha256` is charged for `iterations`.
+    fn pbkdf2_units(iterations: u32) -> u64 {
+        sha256_units(|| {
+            pbkdf2_sha256(b"correct horse 1", &[0; SALT_LEN], iterations);
+        })
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn pbkdf2_matches_known_answers() {
+        // RFC 7914, section 11.
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"password", b"salt", 1)),
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+        );
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"password", b"salt", 4096)),
+            "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+        );
+        // At the default work factor, from Python's `hashlib.pbkdf2_hmac`,
+        // and with a password longer than a block.
+        let salt: [u8; SALT_LEN] = core::array::from_fn(|i| i as u8);
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"correct horse 1", &salt, DEFAULT_PBKDF2_ITERATIONS)),
+            "4d102784ee5939c3696445aa8463b1bdd264fde02aa5edf7024fa753447ed428"
+        );
+        assert_eq!(
+            hex(&pbkdf2_sha256(&[b'x'; 100], &salt, DEFAULT_PBKDF2_ITERATIONS)),
+            "a6e670281aaf7de28484d09a051f88582bab11e9bc7fb89a29a63fb6b3dbb667"
+        );
+    }
+
+    #[test]
+    fn password_keys_round_trip_at_the_default_work_factor() {
+        let salt = [7; SALT_LEN];
+        let keys = derive_password_keys(b"correct horse 1", &salt, DEFAULT_PBKDF2_ITERATIONS);
+        let prehashed = derive_password_key(b"correct horse 1", &salt, DEFAULT_PBKDF2_ITERATIONS);
+        assert_eq!(expand_password_key(&prehashed).verifier, keys.verifier);
+
+        let wrong = derive_password_keys(b"correct horse 2", &salt, DEFAULT_PBKDF2_ITERATIONS);
+        assert_ne!(wrong.verifier, keys.verifier);
+    }
+
+    #[test]
+    fn pbkdf2_syscall_cost() {
+        // Each further iteration hashes [64, 32, 0] bytes, then [64, 32].
+        assert_eq!(pbkdf2_units(2) - pbkdf2_units(1), 276);
+        assert_eq!(pbkdf2_units(DEFAULT_PBKDF2_ITERATIONS), 70_650);
+    }
+
+    #[test]
+    fn recovery_wrap_is_recomputed_from_the_secret() {
+        let secret = [3; RECOVERY_SECRET_LEN];
+        let public_key = derive_recovery_public_key(&secret).unwrap();
+        let data_key = Zeroizing::new([5; 32]);
+        let (ephemeral_key, wrapping_key) = derive_recovery_wrap(&public_key, &data_key, &[0; NONCE_LEN]).unwrap();
+
+        assert_eq!(*derive_recovery_unwrap(&secret, &ephemeral_key).unwrap(), *wrapping_key);
+        let other = derive_recovery_unwrap(&[4; RECOVERY_SECRET_LEN], &ephemeral_key).unwrap();
+        assert_ne!(*other, *wrapping_key);
+
+        // A fresh nonce gives an unrelated ephemeral key.
+        let (next_ephemeral_key, _) = derive_recovery_wrap(&public_key, &data_key, &[1; NONCE_LEN]).unwrap();
+        assert_ne!(next_ephemeral_key, ephemeral_key);
+    }
+
+    #[test]
+    fn points_off_the_curve_are_refused() {
+        let mut point = G1_GENERATOR;
+        point[63] = 3;
+        assert_eq!(
+            derive_recovery_unwrap(&[3; RECOVERY_SECRET_LEN], &point).unwrap_err(),
+            TransacError::DecryptionFailed.into()
+        );
+    }
+
+    #[test]
+    fn encryption_round_trips_only_under_the_same_key_and_label() {
+        let key = Zeroizing::new([1; 32]);
+        let nonce = [2; NONCE_LEN];
+        let ciphertext = encrypt(&key, &nonce, b"main", b"signature").unwrap();
+        assert_eq!(ciphertext.len(), b"signature".len() + TAG_LEN);
+        assert_eq!(*decrypt(&key, &nonce, b"main", &ciphertext).unwrap(), b"signature");
+
+        let failed = TransacError::DecryptionFailed.into();
+        assert_eq!(decrypt(&Zeroizing::new([3; 32]), &nonce, b"main", &ciphertext).unwrap_err(), failed);
+        assert_eq!(decrypt(&key, &nonce, b"other", &ciphertext).unwrap_err(), failed);
+        assert_eq!(decrypt(&key, &[4; NONCE_LEN], b"main", &ciphertext).unwrap_err(), failed);
+    }
+}
@@ -0,0 +1,104 @@
+use solana_program::program_error::ProgramError;
+use thiserror::Error;
+
+/// Errors returned by the program, surfaced to clients as
+/// `ProgramError::Custom(code)`.
+///
+/// | Code | Variant                 |
+/// |------|-------------------------|
+/// | 0    | `WrongPassword`         |
+/// | 1    | `InvalidUtf8`           |
+/// | 2    | `AccountNotInitialized` |
+/// | 3    | `AlreadyInitialized`    |
+/// | 4    | `UnsupportedVersion`    |
+/// | 5    | `Locked`                |
+/// | 6    | `LabelNotFound`         |
+/// | 7    | `DuplicateLabel`        |
+/// | 8    | `SignatureTooLong`      |
+/// | 9    | `WeakPassword`          |
+/// | 10   | `OwnerMismatch`         |
+/// | 11   | `DecryptionFailed`      |
+/// | 12   | `CorruptRecord`         |
+/// | 13   | `Expired`               |
+/// | 14   | `TimeLocked`            |
+/// | 15   | `RecoveryNotConfigured` |
+/// | 16   | `WrongRecoveryKey`      |
+/// | 17   | `SignatureInvalid`      |
+/// | 18   | `MalformedSignature`    |
+/// | 19   | `NotDelegate`           |
+/// | 20   | `DelegationExpired`     |
+/// | 21   | `InsufficientSigners`   |
+/// | 22   | `PasswordReused`        |
+/// | 23   | `MetadataTooLong`       |
+/// | 24   | `StaleNonce`            |
+/// | 25   | `CounterOverflow`       |
+/// | 26   | `RecordFull`            |
+/// | 27   | `InputTooLong`          |
+///
+/// Codes are part of the client-facing interface: append new variants,
+/// never renumber existing ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Error)]
+pub enum TransacError {
+    #[error("Password does not match the stored hash")]
+    WrongPassword = 0,
+    #[error("Input is not valid UTF-8")]
+    InvalidUtf8 = 1,
+    #[error("Record account has not been initialized")]
+    AccountNotInitialized = 2,
+    #[error("Record account is already initialized")]
+    AlreadyInitialized = 3,
+    #[error("Record was written by an unsupported program version")]
+    UnsupportedVersion = 4,
+    #[error("Too many failed attempts, record is temporarily locked")]
+    Locked = 5,
+    #[error("No signature is stored under the requested label")]
+    LabelNotFound = 6,
+    #[error("A signature is already stored under this label")]
+    DuplicateLabel = 7,
+    #[error("Signature exceeds MAX_SIGNATURE_LEN")]
+    SignatureTooLong = 8,
+    #[error("Password does not meet the strength policy")]
+    WeakPassword = 9,
+    #[error("Signer is not the record owner")]
+    OwnerMismatch = 10,
+    #[error("Stored signature failed authentication on decryption")]
+    DecryptionFailed = 11,
+    #[error("Record checksum does not match its contents")]
+    CorruptRecord = 12,
+    #[error("Record has expired and can no longer be viewed")]
+    Expired = 13,
+    #[error("Record cannot be viewed before its unlock time")]
+    TimeLocked = 14,
+    #[error("Record was created without a recovery key")]
+    RecoveryNotConfigured = 15,
+    #[error("Recovery secret does not match the stored hash")]
+    WrongRecoveryKey = 16,
+    #[error("Stored signature does not verify against the message and signer")]
+    SignatureInvalid = 17,
+    #[error("Stored signature is not in the expected format")]
+    MalformedSignature = 18,
+    #[error("Signer is not the record's delegate")]
+    NotDelegate = 19,
+    #[error("Delegated access has expired")]
+    DelegationExpired = 20,
+    #[error("Too few of the record's authorities signed")]
+    InsufficientSigners = 21,
+    #[error("New password matches the current or a recent one")]
+    PasswordReused = 22,
+    #[error("Metadata exceeds MAX_METADATA_LEN")]
+    MetadataTooLong = 23,
+    #[error("View nonce does not match the record's")]
+    StaleNonce = 24,
+    #[error("A record counter would wrap")]
+    CounterOverflow = 25,
+    #[error("Record has no room for another signature")]
+    RecordFull = 26,
+    #[error("An instruction field exceeds its length limit")]
+    InputTooLong = 27,
+}
+
+impl From<TransacError> for ProgramError {
+    fn from(e: TransacError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
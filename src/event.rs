@@ -0,0 +1,30 @@
+//! Structured events for indexers.
+//!
+//! Each event is logged with `sol_log_data` as a single Borsh-encoded
+//! [`TransacEvent`], so it shows up in the transaction logs as one base64
+//! `Program data:` entry whose first byte is the event type. Events carry no
+//! secrets: never a password, key or signature.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum TransacEvent {
+    /// A record was created.
+    RecordCreated { record: Pubkey, owner: Pubkey, timestamp: i64 },
+    /// A password check against the record succeeded.
+    PasswordVerified { record: Pubkey, timestamp: i64 },
+    /// The record's password was replaced, by update or recovery.
+    PasswordChanged { record: Pubkey, timestamp: i64 },
+    /// The record was closed and its lamports reclaimed.
+    RecordClosed { record: Pubkey, timestamp: i64 },
+}
+
+impl TransacEvent {
+    pub fn emit(&self) {
+        // Encoding a handful of fixed-size fields into a Vec can't fail.
+        if let Ok(bytes) = borsh::to_vec(self) {
+            sol_log_data(&[&bytes]);
+        }
+    }
+}
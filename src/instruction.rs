@@ -0,0 +1,647 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+use zeroize::Zeroize;
+
+use crate::{
+    error::TransacError, sigverify::ETH_ADDRESS_LEN, ACCESS_KEY_LEN, MAX_CALLBACK_DATA_LEN, MAX_MESSAGE_LEN,
+    MAX_PASSWORD_LEN, RECOVERY_SECRET_LEN, SALT_LEN,
+};
+
+/// Instructions understood by the program, Borsh-encoded in
+/// `instruction_data`. The leading variant byte is the opcode, so variants
+/// are part of the client-facing interface: append new ones, never reorder.
+///
+/// Passwords are taken as raw bytes and hashed as given, with no UTF-8
+/// decoding, and are at most `MAX_PASSWORD_LEN` bytes. Signatures are
+/// likewise opaque bytes.
+///
+/// A wrong password fails the instruction with `WrongPassword`, except in
+/// the instructions that need no owner signature (the views, the verifies and
+/// `VerifyAndCall`). Those succeed without doing anything, so that the failed
+/// attempt is kept and counts towards the lockout.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum TransacInstruction {
+    /// Creates a record holding the source account's data as its first
+    /// signature, stored under `label`. Return data is the record's address
+    /// followed by its bump seed.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Source account whose data is the signature
+    /// 1. `[writable]` Record account, the PDA for `(owner, label)`
+    /// 2. `[writable, signer]` Owner, who also pays for the record
+    /// 3. `[]` System program
+    Create {
+        /// Caller-supplied entropy the salt is derived from.
+        entropy: [u8; SALT_LEN],
+        /// Record name, used as a PDA seed, so at most `MAX_SEED_LEN` bytes.
+        label: String,
+        password: Vec<u8>,
+        options: CreateOptions,
+    },
+
+    /// Returns the signature stored under `label` through return data. On a
+    /// wrong password there is no return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. .. `[signer]` Authorities co-signing the view, if the record has any
+    View {
+        label: String,
+        password: Vec<u8>,
+        /// Must equal the record's current view nonce.
+        nonce: u64,
+    },
+
+    /// Replaces the record's password.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Record owner
+    UpdatePassword {
+        current_password: Vec<u8>,
+        new_password: Vec<u8>,
+    },
+
+    /// Closes the record and sends its lamports to the destination.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[writable]` Destination for the reclaimed lamports
+    /// 2. `[signer]` Record owner
+    Close { password: Vec<u8> },
+
+    /// Checks the password without revealing anything; return data is a
+    /// single byte, 1 if it matched and 0 otherwise.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    Verify { password: Vec<u8> },
+
+    /// Adds the source account's data to the record under `label`.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Source account whose data is the signature
+    /// 1. `[writable]` Record account
+    /// 2. `[writable, signer]` Record owner, who pays for any extra rent
+    /// 3. `[]` System program
+    AppendSignature { label: String, password: Vec<u8> },
+
+    /// Hands the record over to `new_owner`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Current record owner
+    TransferOwnership { password: Vec<u8>, new_owner: Pubkey },
+
+    /// Replaces a forgotten password using the recovery secret the record was
+    /// created with.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Record owner
+    Recover {
+        recovery_secret: [u8; RECOVERY_SECRET_LEN],
+        new_password: Vec<u8>,
+    },
+
+    /// Creates several records at once, each as `Create` would. Either all
+    /// of them are created or, if any fails, none are. Return data is each
+    /// record's address and bump, as for `Create`, in entry order. Every
+    /// record costs a password derivation, over 70k CU in hashing alone, so
+    /// batches beyond two need a raised compute budget.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable, signer]` Owner, who also pays for the records
+    /// 1. `[]` System program
+    /// 2. .. The source and record accounts, as referenced by the entries
+    CreateBatch { entries: Vec<CreateBatchEntry> },
+
+    /// Succeeds only if the password matches and the signature stored under
+    /// `label` is a valid ed25519 signature by `signer` over `message`.
+    ///
+    /// The check itself is done by the ed25519 precompile, so the instruction
+    /// immediately before this one must be an ed25519 program instruction
+    /// verifying that same signature, which means it is revealed in the
+    /// transaction.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[]` Instructions sysvar
+    /// 2. `[signer]` Record owner
+    VerifyEd25519 {
+        label: String,
+        password: Vec<u8>,
+        message: Vec<u8>,
+        signer: Pubkey,
+    },
+
+    /// Succeeds only if the password matches and the signature stored under
+    /// `label`, a 65-byte recoverable secp256k1 signature, recovers to
+    /// `expected_address` over `message_hash`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Record owner
+    VerifySecp256k1 {
+        label: String,
+        password: Vec<u8>,
+        message_hash: [u8; 32],
+        expected_address: [u8; ETH_ADDRESS_LEN],
+    },
+
+    /// Grants `delegate` read access until its expiry, replacing any earlier
+    /// grant, or revokes it when `None`. Revoking or replacing a grant
+    /// re-encrypts the record under a new data key, which is what cuts the
+    /// old delegate off; an expired grant is only cut off that way too.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Record owner
+    SetDelegate {
+        password: Vec<u8>,
+        delegate: Option<DelegateGrant>,
+    },
+
+    /// Returns the signature stored under `label` to the record's delegate,
+    /// who proves access with a signature and the grant's access key instead
+    /// of the password.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Delegate
+    /// 2. .. `[signer]` Authorities co-signing the view, if the record has any
+    DelegateView {
+        label: String,
+        access_key: [u8; ACCESS_KEY_LEN],
+        /// Must equal the record's current view nonce.
+        nonce: u64,
+    },
+
+    /// Replaces the record's metadata. Metadata is not secret, so only the
+    /// owner's signature is needed, not the password.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[writable, signer]` Record owner, who pays for any extra rent
+    /// 2. `[]` System program
+    SetMetadata { metadata: Vec<u8> },
+
+    /// Checks the password and, only if it matches, invokes the callback
+    /// program with `data` and the remaining accounts, passed on with the
+    /// same signer and writable flags. The callback may not be this program.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[]` Callback program
+    /// 2. .. Accounts forwarded to the callback
+    VerifyAndCall { password: Vec<u8>, data: Vec<u8> },
+
+    /// Replaces the signature stored under `label` with the source account's
+    /// data, keeping the password.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Source account whose data is the new signature
+    /// 1. `[writable]` Record account
+    /// 2. `[writable, signer]` Record owner, who pays for any extra rent
+    /// 3. `[]` System program
+    RotateSignature { label: String, password: Vec<u8> },
+
+    /// As `Verify`, but with the password pre-hashed by the client into
+    /// `PBKDF2-HMAC-SHA256(password, salt, iterations)`, using the record's
+    /// salt and iterations. The plaintext password then never appears in the
+    /// transaction, but the key does, and it grants the same access: treat
+    /// it as a bearer token.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    VerifyPrehashed { password_key: [u8; 32] },
+
+    /// As `View`, with a pre-hashed password as for `VerifyPrehashed`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. .. `[signer]` Authorities co-signing the view, if the record has any
+    ViewPrehashed {
+        label: String,
+        password_key: [u8; 32],
+        /// Must equal the record's current view nonce.
+        nonce: u64,
+    },
+
+    /// As `Create`, but with the signature carried in the instruction itself,
+    /// so no source account is needed. Signature, password and options must
+    /// then fit in one transaction together, so their variable-length parts
+    /// may total at most `MAX_INLINE_LEN` bytes. Return data is as for
+    /// `Create`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account, the PDA for `(owner, label)`
+    /// 1. `[writable, signer]` Owner, who also pays for the record
+    /// 2. `[]` System program
+    CreateInline {
+        entropy: [u8; SALT_LEN],
+        label: String,
+        /// At most `MAX_SIGNATURE_LEN` bytes.
+        signature: Vec<u8>,
+        password: Vec<u8>,
+        options: CreateOptions,
+    },
+
+    /// Deletes the signature stored under `label`.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Record account
+    /// 1. `[signer]` Record owner
+    RemoveSignature { label: String, password: Vec<u8> },
+}
+
+/// Optional settings fixed when a record is created.
+#[derive(Clone, Debug, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreateOptions {
+    /// Unix timestamp after which the record can no longer be viewed.
+    pub expires_at: Option<i64>,
+    /// Unix timestamp before which the record cannot be viewed.
+    pub unlocks_at: Option<i64>,
+    /// Secret that can later reset the password through `Recover`. Only its
+    /// hash and public key are kept, so it should be generated randomly and
+    /// stored offline.
+    pub recovery_secret: Option<[u8; RECOVERY_SECRET_LEN]>,
+    /// Wallets that can co-sign views, at most `MAX_AUTHORITIES` and without
+    /// duplicates.
+    pub authorities: Vec<Pubkey>,
+    /// How many of `authorities` must sign each view; 0 disables the check.
+    pub threshold: u8,
+    /// Public annotation, at most `MAX_METADATA_LEN` bytes.
+    pub metadata: Vec<u8>,
+}
+
+/// One record of a `CreateBatch`. The indices point into the instruction's
+/// account list.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CreateBatchEntry {
+    /// Source account whose data is the signature.
+    pub source_index: u8,
+    /// Record account, the PDA for `(owner, label)`.
+    pub record_index: u8,
+    pub entropy: [u8; SALT_LEN],
+    pub label: String,
+    pub password: Vec<u8>,
+    pub options: CreateOptions,
+}
+
+/// Read access handed to another wallet by `SetDelegate`.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct DelegateGrant {
+    pub delegate: Pubkey,
+    /// Unix timestamp from which `DelegateView` refuses the grant. The
+    /// delegate can still decrypt the account data offline until the grant
+    /// is revoked.
+    pub expires_at: i64,
+    /// Secret the owner shares with the delegate. The record's data key is
+    /// wrapped under it, since a signature alone can't unlock anything.
+    pub access_key: [u8; ACCESS_KEY_LEN],
+}
+
+/// The opcode of each [`TransacInstruction`] variant, for code that only has
+/// the leading byte of `instruction_data` to go on. Declared in variant
+/// order, so that `opcode as u8` is the variant's Borsh tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Opcode {
+    Create,
+    View,
+    UpdatePassword,
+    Close,
+    Verify,
+    AppendSignature,
+    TransferOwnership,
+    Recover,
+    CreateBatch,
+    VerifyEd25519,
+    VerifySecp256k1,
+    SetDelegate,
+    DelegateView,
+    SetMetadata,
+    VerifyAndCall,
+    RotateSignature,
+    VerifyPrehashed,
+    ViewPrehashed,
+    CreateInline,
+    RemoveSignature,
+}
+
+impl Opcode {
+    /// Every opcode, indexed by its value.
+    pub const ALL: [Opcode; 20] = [
+        Opcode::Create,
+        Opcode::View,
+        Opcode::UpdatePassword,
+        Opcode::Close,
+        Opcode::Verify,
+        Opcode::AppendSignature,
+        Opcode::TransferOwnership,
+        Opcode::Recover,
+        Opcode::CreateBatch,
+        Opcode::VerifyEd25519,
+        Opcode::VerifySecp256k1,
+        Opcode::SetDelegate,
+        Opcode::DelegateView,
+        Opcode::SetMetadata,
+        Opcode::VerifyAndCall,
+        Opcode::RotateSignature,
+        Opcode::VerifyPrehashed,
+        Opcode::ViewPrehashed,
+        Opcode::CreateInline,
+        Opcode::RemoveSignature,
+    ];
+
+    /// The opcode with value `opcode`, if there is one.
+    pub fn from_u8(opcode: u8) -> Option<Self> {
+        Self::ALL.get(opcode as usize).copied()
+    }
+}
+
+impl TransacInstruction {
+    /// Opcode of the last variant.
+    pub const LAST_OPCODE: u8 = Opcode::ALL[Opcode::ALL.len() - 1] as u8;
+
+    /// Which variant this is, as encoded in its leading byte.
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            TransacInstruction::Create { .. } => Opcode::Create,
+            TransacInstruction::View { .. } => Opcode::View,
+            TransacInstruction::UpdatePassword { .. } => Opcode::UpdatePassword,
+            TransacInstruction::Close { .. } => Opcode::Close,
+            TransacInstruction::Verify { .. } => Opcode::Verify,
+            TransacInstruction::AppendSignature { .. } => Opcode::AppendSignature,
+            TransacInstruction::TransferOwnership { .. } => Opcode::TransferOwnership,
+            TransacInstruction::Recover { .. } => Opcode::Recover,
+            TransacInstruction::CreateBatch { .. } => Opcode::CreateBatch,
+            TransacInstruction::VerifyEd25519 { .. } => Opcode::VerifyEd25519,
+            TransacInstruction::VerifySecp256k1 { .. } => Opcode::VerifySecp256k1,
+            TransacInstruction::SetDelegate { .. } => Opcode::SetDelegate,
+            TransacInstruction::DelegateView { .. } => Opcode::DelegateView,
+            TransacInstruction::SetMetadata { .. } => Opcode::SetMetadata,
+            TransacInstruction::VerifyAndCall { .. } => Opcode::VerifyAndCall,
+            TransacInstruction::RotateSignature { .. } => Opcode::RotateSignature,
+            TransacInstruction::VerifyPrehashed { .. } => Opcode::VerifyPrehashed,
+            TransacInstruction::ViewPrehashed { .. } => Opcode::ViewPrehashed,
+            TransacInstruction::CreateInline { .. } => Opcode::CreateInline,
+            TransacInstruction::RemoveSignature { .. } => Opcode::RemoveSignature,
+        }
+    }
+
+    /// Decodes `instruction_data`, logging which opcode it carried if that
+    /// fails, so a misencoded client can be told apart from one that is
+    /// ahead of the deployed program.
+    pub fn unpack(instruction_data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(instruction_data).map_err(|_| {
+            match instruction_data.first() {
+                None => msg!("Instruction data is empty"),
+                Some(&opcode) if opcode > Self::LAST_OPCODE => msg!("Unknown opcode {}", opcode),
+                Some(&opcode) => msg!("Malformed data for opcode {}", opcode),
+            }
+            ProgramError::InvalidInstructionData
+        })
+    }
+
+    /// Refuses passwords over [`MAX_PASSWORD_LEN`], messages over
+    /// [`MAX_MESSAGE_LEN`] and callback data over [`MAX_CALLBACK_DATA_LEN`]
+    /// with [`TransacError::InputTooLong`]. Run before anything is hashed,
+    /// so no field costs more than its cap allows.
+    pub fn check_lengths(&self) -> Result<(), ProgramError> {
+        let check = |field: &str, len: usize, max: usize| {
+            if len > max {
+                msg!("{} exceeds {} bytes", field, max);
+                return Err(ProgramError::from(TransacError::InputTooLong));
+            }
+            Ok(())
+        };
+        let check_password = |password: &[u8]| check("Password", password.len(), MAX_PASSWORD_LEN);
+
+        match self {
+            TransacInstruction::Create { password, .. }
+            | TransacInstruction::CreateInline { password, .. }
+            | TransacInstruction::View { password, .. }
+            | TransacInstruction::Close { password }
+            | TransacInstruction::AppendSignature { password, .. }
+            | TransacInstruction::Verify { password }
+            | TransacInstruction::TransferOwnership { password, .. }
+            | TransacInstruction::RotateSignature { password, .. }
+            | TransacInstruction::RemoveSignature { password, .. }
+            | TransacInstruction::VerifySecp256k1 { password, .. }
+            | TransacInstruction::SetDelegate { password, .. } => check_password(password),
+            TransacInstruction::UpdatePassword {
+                current_password,
+                new_password,
+            } => {
+                check_password(current_password)?;
+                check_password(new_password)
+            }
+            TransacInstruction::Recover { new_password, .. } => check_password(new_password),
+            TransacInstruction::CreateBatch { entries } => {
+                entries.iter().try_for_each(|entry| check_password(&entry.password))
+            }
+            TransacInstruction::VerifyEd25519 { password, message, .. } => {
+                check_password(password)?;
+                check("Message", message.len(), MAX_MESSAGE_LEN)
+            }
+            TransacInstruction::VerifyAndCall { password, data } => {
+                check_password(password)?;
+                check("Callback data", data.len(), MAX_CALLBACK_DATA_LEN)
+            }
+            TransacInstruction::DelegateView { .. }
+            | TransacInstruction::SetMetadata { .. }
+            | TransacInstruction::VerifyPrehashed { .. }
+            | TransacInstruction::ViewPrehashed { .. } => Ok(()),
+        }
+    }
+}
+
+// Passwords and other secrets are wiped as soon as the decoded instruction
+// goes out of scope.
+impl Drop for TransacInstruction {
+    fn drop(&mut self) {
+        match self {
+            TransacInstruction::Create { password, options, .. }
+            | TransacInstruction::CreateInline { password, options, .. } => {
+                password.zeroize();
+                options.recovery_secret.zeroize();
+            }
+            TransacInstruction::View { password, .. }
+            | TransacInstruction::Close { password }
+            | TransacInstruction::AppendSignature { password, .. }
+            | TransacInstruction::RotateSignature { password, .. }
+            | TransacInstruction::RemoveSignature { password, .. }
+            | TransacInstruction::Verify { password }
+            | TransacInstruction::VerifyAndCall { password, .. }
+            | TransacInstruction::TransferOwnership { password, .. }
+            | TransacInstruction::VerifyEd25519 { password, .. }
+            | TransacInstruction::VerifySecp256k1 { password, .. } => password.zeroize(),
+            TransacInstruction::UpdatePassword {
+                current_password,
+                new_password,
+            } => {
+                current_password.zeroize();
+                new_password.zeroize();
+            }
+            TransacInstruction::Recover {
+                recovery_secret,
+                new_password,
+            } => {
+                recovery_secret.zeroize();
+                new_password.zeroize();
+            }
+            TransacInstruction::SetDelegate { password, delegate } => {
+                password.zeroize();
+                if let Some(grant) = delegate {
+                    grant.access_key.zeroize();
+                }
+            }
+            TransacInstruction::DelegateView { access_key, .. } => access_key.zeroize(),
+            TransacInstruction::SetMetadata { .. } => {}
+            TransacInstruction::VerifyPrehashed { password_key }
+            | TransacInstruction::ViewPrehashed { password_key, .. } => password_key.zeroize(),
+            TransacInstruction::CreateBatch { entries } => {
+                for entry in entries {
+                    entry.password.zeroize();
+                    entry.options.recovery_secret.zeroize();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// One instance of every variant, in opcode order.
+    pub(crate) fn every_variant() -> Vec<TransacInstruction> {
+        let password = b"correct horse 1".to_vec();
+        let label = "main".to_string();
+        let options = CreateOptions {
+            expires_at: Some(2),
+            unlocks_at: Some(1),
+            recovery_secret: Some([9; RECOVERY_SECRET_LEN]),
+            authorities: vec![Pubkey::new_unique()],
+            threshold: 1,
+            metadata: b"note".to_vec(),
+        };
+        vec![
+            TransacInstruction::Create {
+                entropy: [1; SALT_LEN],
+                label: label.clone(),
+                password: password.clone(),
+                options: options.clone(),
+            },
+            TransacInstruction::View {
+                label: label.clone(),
+                password: password.clone(),
+                nonce: 3,
+            },
+            TransacInstruction::UpdatePassword {
+                current_password: password.clone(),
+                new_password: b"battery staple 2".to_vec(),
+            },
+            TransacInstruction::Close { password: password.clone() },
+            TransacInstruction::Verify { password: password.clone() },
+            TransacInstruction::AppendSignature {
+                label: label.clone(),
+                password: password.clone(),
+            },
+            TransacInstruction::TransferOwnership {
+                password: password.clone(),
+                new_owner: Pubkey::new_unique(),
+            },
+            TransacInstruction::Recover {
+                recovery_secret: [9; RECOVERY_SECRET_LEN],
+                new_password: b"battery staple 2".to_vec(),
+            },
+            TransacInstruction::CreateBatch {
+                entries: vec![CreateBatchEntry {
+                    source_index: 2,
+                    record_index: 3,
+                    entropy: [2; SALT_LEN],
+                    label: label.clone(),
+                    password: password.clone(),
+                    options: options.clone(),
+                }],
+            },
+            TransacInstruction::VerifyEd25519 {
+                label: label.clone(),
+                password: password.clone(),
+                message: b"message".to_vec(),
+                signer: Pubkey::new_unique(),
+            },
+            TransacInstruction::VerifySecp256k1 {
+                label: label.clone(),
+                password: password.clone(),
+                message_hash: [4; 32],
+                expected_address: [5; ETH_ADDRESS_LEN],
+            },
+            TransacInstruction::SetDelegate {
+                password: password.clone(),
+                delegate: Some(DelegateGrant {
+                    delegate: Pubkey::new_unique(),
+                    expires_at: 6,
+                    access_key: [7; ACCESS_KEY_LEN],
+                }),
+            },
+            TransacInstruction::DelegateView {
+                label: label.clone(),
+                access_key: [7; ACCESS_KEY_LEN],
+                nonce: 8,
+            },
+            TransacInstruction::SetMetadata { metadata: b"note".to_vec() },
+            TransacInstruction::VerifyAndCall {
+                password: password.clone(),
+                data: b"data".to_vec(),
+            },
+            TransacInstruction::RotateSignature {
+                label: label.clone(),
+                password: password.clone(),
+            },
+            TransacInstruction::VerifyPrehashed { password_key: [10; 32] },
+            TransacInstruction::ViewPrehashed {
+                label: label.clone(),
+                password_key: [10; 32],
+                nonce: 11,
+            },
+            TransacInstruction::CreateInline {
+                entropy: [3; SALT_LEN],
+                label: label.clone(),
+                signature: b"sig".to_vec(),
+                password: password.clone(),
+                options,
+            },
+            TransacInstruction::RemoveSignature { label, password },
+        ]
+    }
+
+    #[test]
+    fn opcodes_are_indexed_by_value() {
+        for (value, opcode) in Opcode::ALL.iter().enumerate() {
+            assert_eq!(*opcode as usize, value);
+            assert_eq!(Opcode::from_u8(value as u8), Some(*opcode));
+        }
+        assert_eq!(Opcode::from_u8(TransacInstruction::LAST_OPCODE + 1), None);
+        // Verify-only was specified as opcode 4.
+        assert_eq!(Opcode::Verify as u8, 4);
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        let variants = every_variant();
+        assert_eq!(variants.len(), Opcode::ALL.len());
+        for (value, instruction) in variants.iter().enumerate() {
+            let bytes = borsh::to_vec(instruction).unwrap();
+            assert_eq!(bytes[0] as usize, value);
+            assert_eq!(instruction.opcode() as usize, value);
+            assert_eq!(TransacInstruction::unpack(&bytes).unwrap(), *instruction);
+            // Truncated data is malformed rather than misread.
+            let truncated = TransacInstruction::unpack(&bytes[..bytes.len() - 1]);
+            assert_eq!(truncated, Err(ProgramError::InvalidInstructionData));
+        }
+    }
+}
@@ -1,70 +1,2833 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::AccountInfo,
-    entrypoint,
-    entrypoint::ProgramResult,
-    pubkey::Pubkey,
+    borsh1::get_instance_packed_len,
+    instruction::{AccountMeta, Instruction},
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    program::{invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
+    pubkey::{Pubkey, MAX_SEED_LEN},
     msg,
-    program_error::ProgramError
+    program_error::ProgramError,
+    system_instruction,
+    sysvar::{clock::Clock, instructions::get_instruction_relative, rent::Rent, Sysvar},
 };
-use sha2::{Sha256, Digest};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
 
-#[derive(Debug)]
+#[cfg(feature = "client")]
+pub mod client;
+mod crypto;
+pub mod error;
+pub mod event;
+pub mod instruction;
+mod sigverify;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+
+use crate::{
+    crypto::{
+        decrypt, derive_access_wrapping_key, derive_data_key, derive_password_keys, derive_recovery_public_key,
+        derive_recovery_unwrap, derive_recovery_verifier, derive_recovery_wrap, derive_salt, encrypt,
+        expand_password_key, sha256, EncryptionKey, NONCE_LEN, RECOVERY_POINT_LEN, TAG_LEN,
+    },
+    error::TransacError,
+    event::TransacEvent,
+    instruction::{CreateOptions, DelegateGrant, TransacInstruction},
+    sigverify::{check_ed25519_instruction, recover_secp256k1_address},
+};
+
+/// Layout version written into the first byte of every record.
+pub const RECORD_VERSION: u8 = 1;
+
+/// PBKDF2 iterations used for newly set passwords.
+///
+/// Each iteration is one HMAC, that is two `sol_sha256` calls, which the
+/// runtime's default cost table charges 276 compute units between them. The
+/// default therefore spends 70,650 CU on hashing per password check, as the
+/// `pbkdf2_syscall_cost` test tallies. The program's own instructions in each
+/// iteration, the XOR and the call setup, come on top and have not been
+/// measured on SBF. That leaves a margin inside the 200k per-instruction
+/// default. At the full 1.4M CU budget, hashing alone caps a password at
+/// about 5,000 iterations, and fewer are affordable in practice.
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 256;
+
+/// Wrong passwords tolerated before the record locks. Only the unsigned
+/// paths (views, verifies and `VerifyAndCall`) count; see
+/// `authenticate_credential` for how this lets anyone lock a record.
+pub const MAX_FAILED_ATTEMPTS: u8 = 5;
+
+/// How long a lockout lasts, in slots (roughly ten minutes).
+pub const LOCKOUT_SLOTS: u64 = 1_500;
+
+/// Longest signature a record accepts, in bytes. Capped at the return-data
+/// limit so that any stored signature can be handed back by a view.
+pub const MAX_SIGNATURE_LEN: usize = MAX_RETURN_DATA;
+
+/// Shortest password accepted when setting one, in bytes.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Distinct character classes (lowercase, uppercase, digits, other) a new
+/// password has to mix.
+pub const MIN_PASSWORD_CLASSES: usize = 2;
+
+/// Seed prefix for record account addresses, see [`derive_record_address`].
+pub const RECORD_SEED: &[u8] = b"record";
+
+/// Length of record salts, and of the entropy `Create` derives them from.
+pub const SALT_LEN: usize = 16;
+
+/// Length of the checksum trailer that follows every stored record.
+pub const CHECKSUM_LEN: usize = 4;
+
+/// Length of the secret that can reset a forgotten password.
+pub const RECOVERY_SECRET_LEN: usize = 32;
+
+/// Length of the access key a delegate views with.
+pub const ACCESS_KEY_LEN: usize = 32;
+
+/// Most authorities a record can require to co-sign views.
+pub const MAX_AUTHORITIES: usize = 11;
+
+/// Previous password verifiers kept to stop a password being reused.
+pub const PASSWORD_HISTORY_LEN: usize = 5;
+
+/// Longest metadata a record accepts, in bytes.
+pub const MAX_METADATA_LEN: usize = 256;
+
+/// Longest `instruction_data` accepted, in bytes: the most a transaction can
+/// carry. This only ever refuses instructions issued through CPI, before
+/// anything is decoded; the per-field caps below are what bound the work a
+/// single field can cause, see [`TransacInstruction::check_lengths`].
+pub const MAX_INSTRUCTION_LEN: usize = 1232;
+
+/// Longest password accepted anywhere, in bytes.
+pub const MAX_PASSWORD_LEN: usize = 128;
+
+/// Longest message `VerifyEd25519` accepts, in bytes.
+pub const MAX_MESSAGE_LEN: usize = 512;
+
+/// Longest data `VerifyAndCall` forwards to its callback, in bytes.
+pub const MAX_CALLBACK_DATA_LEN: usize = 1024;
+
+/// Most bytes `CreateInline` accepts across its signature, password, label,
+/// metadata and authorities together: about what a transaction has room for
+/// once its signature, accounts and the rest of the encoding are counted.
+pub const MAX_INLINE_LEN: usize = 900;
+
+/// Most labelled signatures a record holds.
+pub const MAX_LABELS: usize = 16;
+
+/// Most signature bytes a record holds across all its labels.
+pub const MAX_TOTAL_SIGNATURE_LEN: usize = 8 * MAX_SIGNATURE_LEN;
+
+/// Associated data binding each wrapped copy of the data key to the secret
+/// it is wrapped under.
+const PASSWORD_WRAP_AAD: &[u8] = b"password";
+const RECOVERY_WRAP_AAD: &[u8] = b"recovery";
+const DELEGATE_WRAP_AAD: &[u8] = b"delegate";
+
+/// Encoded size of a [`WrappedKey`] holding a 32-byte key.
+const WRAPPED_KEY_LEN: usize = NONCE_LEN + 4 + 32 + TAG_LEN;
+
+/// Encoded size of a [`Delegate`]: pubkey, expiry, then the wrapped key.
+const DELEGATE_LEN: usize = 32 + 8 + WRAPPED_KEY_LEN;
+
+/// Smallest a stored record can be, checksum included: every `Vec` empty and
+/// every `Option` `None`, field by field as in the [`SignatureRecord`] layout.
+pub const MIN_RECORD_LEN: usize =
+    // `version` to `data_key`
+    1 + 32 + 4 + 8 + SALT_LEN + 4 + 32 + WRAPPED_KEY_LEN
+    // `failed_attempts` to `view_count`
+    + 1 + 8 + 8 + 8 + 1 + 1 + 4
+    // `recovery` to `view_nonce`
+    + 1 + 1 + 4 + 1 + 4 + 4 + 8
+    // `creator` to `bump`
+    + 32 + 4 + 1
+    + CHECKSUM_LEN;
+
+/// What a caller proves knowledge of the password with.
+#[derive(Clone, Copy, Debug)]
+pub enum Credential<'a> {
+    /// The password itself.
+    Password(&'a [u8]),
+    /// `PBKDF2-HMAC-SHA256(password, salt, iterations)`, computed by the
+    /// client so that the password never leaves it. Whoever holds this value
+    /// has the same access as the password's holder: it is a bearer token
+    /// that only spares the password itself from exposure.
+    PasswordKey(&'a [u8; 32]),
+}
+
+/// Password-protected signature stored in a record account.
+///
+/// Accounts hold the Borsh encoding of this struct, in field order:
+///
+/// | Field               | Encoding                                              |
+/// |---------------------|-------------------------------------------------------|
+/// | `version`           | 1 byte, currently [`RECORD_VERSION`]                  |
+/// | `owner`             | 32-byte pubkey                                        |
+/// | `signatures`        | `u32` LE count, then each [`SignatureEntry`]          |
+/// | `next_nonce`        | `u64` LE                                              |
+/// | `salt`              | 16 raw bytes                                          |
+/// | `iterations`        | `u32` LE PBKDF2 iteration count                       |
+/// | `password_hash`     | 32-byte password verifier                             |
+/// | `data_key`          | [`WrappedKey`] under the password                     |
+/// | `failed_attempts`   | 1 byte                                                |
+/// | `locked_until_slot` | `u64` LE                                              |
+/// | `created_at`        | `i64` LE unix timestamp                               |
+/// | `last_accessed_at`  | `i64` LE unix timestamp, 0 until first access         |
+/// | `expires_at`        | `Option<i64>`: tag byte, then the unix timestamp if 1 |
+/// | `unlocks_at`        | `Option<i64>`, as above                               |
+/// | `view_count`        | `u32` LE                                              |
+/// | `recovery`          | `Option<`[`Recovery`]`>`                              |
+/// | `delegate`          | `Option<`[`Delegate`]`>`                              |
+/// | `authorities`       | `u32` LE count, then 32-byte pubkeys                  |
+/// | `threshold`         | 1 byte, 0 if views need no authorities                |
+/// | `password_history`  | `u32` LE count, then 32-byte verifiers, oldest first  |
+/// | `metadata`          | `u32` LE length, then the bytes                       |
+/// | `view_nonce`        | `u64` LE                                              |
+/// | `creator`           | 32-byte pubkey                                        |
+/// | `seed_label`        | string                                                |
+/// | `bump`              | 1 byte                                                |
+///
+/// The record is followed by a [`CHECKSUM_LEN`]-byte trailer, the leading
+/// bytes of the SHA256 of everything before it. Strings are a `u32` LE byte
+/// length followed by the UTF-8 bytes. Any bytes after the trailer are
+/// ignored, so the account may be allocated larger than the record it holds.
+///
+/// Counters never wrap. Tallies (`failed_attempts`, `view_count`) saturate,
+/// since a pinned count is harmless. Nonces (`next_nonce`, `view_nonce`)
+/// fail with [`TransacError::CounterOverflow`] instead, since a repeated
+/// nonce would be unsafe.
+///
+/// Signatures are encrypted under a per-record data key that is only stored
+/// wrapped. With `K = PBKDF2-HMAC-SHA256(password, salt, iterations)`, the
+/// verifier is `SHA256("transac-verifier" || K)` and `data_key` is wrapped
+/// under `SHA256("transac-wrapping" || K)`. A delegate's access key wraps a
+/// further copy the same way, minus the PBKDF2, and a recovery secret one
+/// through its public key, see [`Recovery`], so each of them can reach the
+/// signatures. Changing the password replaces the data key altogether.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
 pub struct SignatureRecord {
-    signature: String,
+    version: u8,
+    /// Wallet that must co-sign destructive operations.
+    owner: Pubkey,
+    /// Encrypted signatures, in insertion order.
+    signatures: Vec<SignatureEntry>,
+    /// Counter the next encryption nonce is taken from.
+    next_nonce: u64,
+    salt: [u8; SALT_LEN],
+    /// Work factor `password_hash` was derived with. Stored per record so the
+    /// default can be raised without invalidating existing records.
+    iterations: u32,
     password_hash: [u8; 32],
+    /// Data key, wrapped under the password.
+    data_key: WrappedKey,
+    failed_attempts: u8,
+    locked_until_slot: u64,
+    created_at: i64,
+    /// Time of the last successful password check.
+    last_accessed_at: i64,
+    /// Past this time viewing is refused, whatever the password.
+    expires_at: Option<i64>,
+    /// Before this time viewing is refused, whatever the password.
+    unlocks_at: Option<i64>,
+    /// Successful views so far, saturating at `u32::MAX`.
+    view_count: u32,
+    /// Set if the record was created with a recovery secret.
+    recovery: Option<Recovery>,
+    /// Wallet currently allowed to view without the password.
+    delegate: Option<Delegate>,
+    /// Wallets whose co-signatures views require.
+    authorities: Vec<Pubkey>,
+    /// How many of `authorities` must sign each view.
+    threshold: u8,
+    /// Verifiers of up to [`PASSWORD_HISTORY_LEN`] earlier passwords.
+    password_history: Vec<[u8; 32]>,
+    /// Client annotation, stored in the clear. Not covered by the password.
+    metadata: Vec<u8>,
+    /// Nonce the next view must carry. Unrelated to `next_nonce`, which
+    /// feeds encryption.
+    view_nonce: u64,
+    /// Owner the record's address was derived from. Unlike `owner`, it is
+    /// kept through transfers, as the address can't move.
+    creator: Pubkey,
+    /// Label the record's address was derived from, kept even if the entry
+    /// carrying it is removed.
+    seed_label: String,
+    /// Bump seed of the record's address.
+    bump: u8,
 }
 
-entrypoint!(process_instruction);
+/// A labelled signature, encrypted with ChaCha20-Poly1305 under the record's
+/// data key. The label is bound in as associated
+/// data.
+///
+/// Encoded as the label string, the 12-byte nonce, then the ciphertext
+/// (tag included) as a `u32` LE length and bytes.
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+pub struct SignatureEntry {
+    label: String,
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+impl SignatureEntry {
+    /// Length of the signature this entry decrypts to.
+    fn plaintext_len(&self) -> usize {
+        self.ciphertext.len().saturating_sub(TAG_LEN)
+    }
+}
+
+/// The record's data key, encrypted with ChaCha20-Poly1305 under a key
+/// derived from the password, the recovery public key or an access key.
+///
+/// Encoded as the 12-byte nonce, then the ciphertext as a `u32` LE length and
+/// bytes.
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+pub struct WrappedKey {
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+/// Lets whoever holds the recovery secret set a new password without the
+/// old one.
+///
+/// The data key is wrapped to a public key rather than under the secret
+/// itself, so that the program can wrap each new data key without being
+/// given the secret. With the scalar `r = SHA256("transac-recovery-scalar" ||
+/// secret)`, its top three bits cleared, `public_key` is `r·G` on alt_bn128.
+/// Each wrap picks an ephemeral scalar `e`, stores `e·G` as `ephemeral_key`
+/// and wraps under `SHA256("transac-recovery-wrapping" || e·r·G)`.
+///
+/// Encoded as the 32-byte recovery key hash, the 64-byte public key, the
+/// 64-byte ephemeral key, then the [`WrappedKey`]. Points are big-endian
+/// `x || y`.
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+pub struct Recovery {
+    /// `SHA256("transac-recovery-verifier" || secret)`.
+    recovery_key_hash: [u8; 32],
+    public_key: [u8; RECOVERY_POINT_LEN],
+    /// Ephemeral key `data_key` was wrapped with.
+    ephemeral_key: [u8; RECOVERY_POINT_LEN],
+    /// Data key, wrapped to `public_key`.
+    data_key: WrappedKey,
+}
 
-fn process_instruction(
+/// Read access granted to another wallet. The expiry bounds `DelegateView`
+/// but not what the delegate can decrypt offline, which lasts until the
+/// grant is revoked, see [`SignatureRecord::revoke_delegate`].
+///
+/// Encoded as the 32-byte delegate pubkey, the `i64` LE expiry, then the
+/// [`WrappedKey`].
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize)]
+pub struct Delegate {
+    delegate: Pubkey,
+    /// Unix timestamp from which the delegate can no longer view.
+    expires_at: i64,
+    /// Data key, wrapped under the grant's access key.
+    data_key: WrappedKey,
+}
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let operation = instruction_data[0];
-    let password = String::from_utf8(instruction_data[1..].to_vec()).map_err(|_| ProgramError::InvalidArgument)?;
-
-    match operation {
-        0 => {
-            let signature = String::from_utf8(accounts[0].data.borrow().to_vec())
-                .map_err(|_| ProgramError::InvalidArgument)?;
-            
-            let record = SignatureRecord::create_signature(signature, &password);
-            accounts[1].serialize_data(&record)?;
+    if instruction_data.len() > MAX_INSTRUCTION_LEN {
+        msg!("Instruction data exceeds {} bytes", MAX_INSTRUCTION_LEN);
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let instruction = TransacInstruction::unpack(instruction_data)?;
+    instruction.check_lengths()?;
+
+    match &instruction {
+        TransacInstruction::Create { entropy, label, password, options } => {
+            if accounts.len() < 4 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let signature = read_signature(&accounts[0])?;
+            let created = create_record(program_id, &accounts[1..4], &signature, entropy, label, password, options)?;
+            set_return_data(&created);
+            msg!("Signature created successfully");
+        }
+        TransacInstruction::CreateInline {
+            entropy,
+            label,
+            signature,
+            password,
+            options,
+        } => {
+            if accounts.len() < 3 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            if signature.len() > MAX_SIGNATURE_LEN {
+                return Err(TransacError::SignatureTooLong.into());
+            }
+            let inline_len = signature.len()
+                + password.len()
+                + label.len()
+                + options.metadata.len()
+                + options.authorities.len() * 32;
+            if inline_len > MAX_INLINE_LEN {
+                msg!("Inline fields total {} bytes, over {}", inline_len, MAX_INLINE_LEN);
+                return Err(TransacError::InputTooLong.into());
+            }
+            let created = create_record(program_id, &accounts[..3], signature, entropy, label, password, options)?;
+            set_return_data(&created);
             msg!("Signature created successfully");
         }
-        1 => {
-            let record = accounts[0].deserialize_data::<SignatureRecord>()?;
-            let signature = record.verify_and_view(&password)?;
-            msg!("Authorized access. Signature: {}", signature);
+        TransacInstruction::CreateBatch { entries } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            // Any failure aborts the whole instruction, so either every
+            // record in the batch is created or none is.
+            let mut created = Vec::with_capacity(entries.len() * 33);
+            for entry in entries {
+                let source = accounts
+                    .get(entry.source_index as usize)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let record = accounts
+                    .get(entry.record_index as usize)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let signature = read_signature(source)?;
+                let record_accounts = [record.clone(), accounts[0].clone(), accounts[1].clone()];
+                created.extend(create_record(
+                    program_id,
+                    &record_accounts,
+                    &signature,
+                    &entry.entropy,
+                    &entry.label,
+                    &entry.password,
+                    &entry.options,
+                )?);
+            }
+            set_return_data(&created);
+            msg!("{} signatures created", entries.len());
+        }
+        TransacInstruction::View { label, password, nonce } => {
+            view(program_id, accounts, label, *nonce, Credential::Password(password))?;
+        }
+        TransacInstruction::UpdatePassword { current_password, new_password } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            validate_password_strength(new_password)?;
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[1])?;
+            let key = authenticate(&accounts[0], &mut record, current_password)?.data_key;
+            record.set_password(&key, new_password)?;
+            record.save(&accounts[0])?;
+            TransacEvent::PasswordChanged {
+                record: *accounts[0].key,
+                timestamp: Clock::get()?.unix_timestamp,
+            }
+            .emit();
+            msg!("Password updated successfully");
+        }
+        TransacInstruction::Close { password } => {
+            if accounts.len() < 3 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[2])?;
+            authenticate(&accounts[0], &mut record, password)?;
+
+            close_account(&accounts[0], &accounts[1])?;
+            TransacEvent::RecordClosed {
+                record: *accounts[0].key,
+                timestamp: Clock::get()?.unix_timestamp,
+            }
+            .emit();
+            msg!("Record closed");
+        }
+        TransacInstruction::AppendSignature { label, password } => {
+            if accounts.len() < 4 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let signature = read_signature(&accounts[0])?;
+
+            let mut record = load_record(&accounts[1], program_id)?;
+            record.check_owner_signed(&accounts[2])?;
+            let key = authenticate(&accounts[1], &mut record, password)?.data_key;
+            record.append_signature(&key, label.clone(), &signature)?;
+
+            let space = record.allocation_len()?;
+            resize_record(&accounts[1], &accounts[2], &accounts[3], space)?;
+            check_rent_exempt(&accounts[1])?;
+            record.save(&accounts[1])?;
+            msg!("Signature appended");
+        }
+        TransacInstruction::RotateSignature { label, password } => {
+            if accounts.len() < 4 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let signature = read_signature(&accounts[0])?;
+
+            let mut record = load_record(&accounts[1], program_id)?;
+            record.check_owner_signed(&accounts[2])?;
+            let key = authenticate(&accounts[1], &mut record, password)?.data_key;
+            record.rotate_signature(&key, label, &signature)?;
+
+            let space = record.allocation_len()?;
+            resize_record(&accounts[1], &accounts[2], &accounts[3], space)?;
+            check_rent_exempt(&accounts[1])?;
+            record.save(&accounts[1])?;
+            msg!("Signature rotated");
+        }
+        TransacInstruction::RemoveSignature { label, password } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[1])?;
+            authenticate(&accounts[0], &mut record, password)?;
+            record.remove_signature(label)?;
+            record.save(&accounts[0])?;
+            msg!("Signature removed");
+        }
+        TransacInstruction::SetMetadata { metadata } => {
+            if accounts.len() < 3 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            // Metadata is public by design, so owning the record is enough.
+            record.check_owner_signed(&accounts[1])?;
+            record.set_metadata(metadata.clone())?;
+
+            let space = record.allocation_len()?;
+            resize_record(&accounts[0], &accounts[1], &accounts[2], space)?;
+            check_rent_exempt(&accounts[0])?;
+            record.save(&accounts[0])?;
+            msg!("Metadata updated");
+        }
+        TransacInstruction::Verify { password } => {
+            verify(program_id, accounts, Credential::Password(password))?;
+        }
+        TransacInstruction::VerifyPrehashed { password_key } => {
+            verify(program_id, accounts, Credential::PasswordKey(password_key))?;
+        }
+        TransacInstruction::ViewPrehashed { label, password_key, nonce } => {
+            view(program_id, accounts, label, *nonce, Credential::PasswordKey(password_key))?;
+        }
+        TransacInstruction::VerifyAndCall { password, data } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            // The runtime already refuses indirect re-entry from the callback;
+            // a direct self-invocation is the one case it allows, so refuse
+            // that here.
+            let callback_program = &accounts[1];
+            if callback_program.key == program_id || !callback_program.executable {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            if authenticate_credential(&accounts[0], &mut record, Credential::Password(password))?.is_none() {
+                return Ok(());
+            }
+            // Persist before handing control away, so the callback sees the
+            // record as it stands after this check.
+            record.save(&accounts[0])?;
+
+            let callback_accounts = &accounts[2..];
+            let callback_ix = Instruction {
+                program_id: *callback_program.key,
+                accounts: callback_accounts
+                    .iter()
+                    .map(|account| AccountMeta {
+                        pubkey: *account.key,
+                        is_signer: account.is_signer,
+                        is_writable: account.is_writable,
+                    })
+                    .collect(),
+                data: data.clone(),
+            };
+            let mut infos = callback_accounts.to_vec();
+            infos.push(callback_program.clone());
+            invoke(&callback_ix, &infos)?;
+            msg!("Callback invoked");
+        }
+        TransacInstruction::TransferOwnership { password, new_owner } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[1])?;
+            let unlocked = authenticate(&accounts[0], &mut record, password)?;
+            record.owner = *new_owner;
+            // Grants are the old owner's to give; the new one starts clean.
+            record.revoke_delegate(&unlocked.data_key, &unlocked.wrapping_key)?;
+            record.save(&accounts[0])?;
+            msg!("Ownership transferred to {}", new_owner);
+        }
+        TransacInstruction::Recover { recovery_secret, new_password } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            validate_password_strength(new_password)?;
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[1])?;
+            record.recover(recovery_secret, new_password)?;
+            record.save(&accounts[0])?;
+            TransacEvent::PasswordChanged {
+                record: *accounts[0].key,
+                timestamp: Clock::get()?.unix_timestamp,
+            }
+            .emit();
+            msg!("Password reset with recovery key");
+        }
+        TransacInstruction::VerifyEd25519 { label, password, message, signer } => {
+            if accounts.len() < 3 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[2])?;
+            let key = authenticate(&accounts[0], &mut record, password)?.data_key;
+            let signature = record.decrypt_signature(&key, label)?;
+            let precompile_ix = get_instruction_relative(-1, &accounts[1])?;
+            check_ed25519_instruction(&precompile_ix, signer, message, &signature)?;
+            record.save(&accounts[0])?;
+            msg!("Signature verified");
+        }
+        TransacInstruction::SetDelegate { password, delegate } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[1])?;
+            let unlocked = authenticate(&accounts[0], &mut record, password)?;
+            record.set_delegate(
+                &unlocked.data_key,
+                &unlocked.wrapping_key,
+                delegate.as_ref(),
+                Clock::get()?.unix_timestamp,
+            )?;
+            record.save(&accounts[0])?;
+            match delegate {
+                Some(grant) => msg!("Delegated view access to {}", grant.delegate),
+                None => msg!("Delegate revoked"),
+            }
+        }
+        TransacInstruction::DelegateView { label, access_key, nonce } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_view_nonce(*nonce)?;
+            let clock = Clock::get()?;
+            record.check_viewable(&clock)?;
+            record.check_authorities(&accounts[2..])?;
+            let key = record.delegate_key(&accounts[1], access_key, &clock)?;
+            let signature = record.decrypt_signature(&key, label)?;
+            record.record_view()?;
+            record.save(&accounts[0])?;
+            set_return_data(&signature);
+            msg!("Authorized delegate access");
+        }
+        TransacInstruction::VerifySecp256k1 { label, password, message_hash, expected_address } => {
+            if accounts.len() < 2 {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut record = load_record(&accounts[0], program_id)?;
+            record.check_owner_signed(&accounts[1])?;
+            let key = authenticate(&accounts[0], &mut record, password)?.data_key;
+            let signature = record.decrypt_signature(&key, label)?;
+            if recover_secp256k1_address(message_hash, &signature)? != *expected_address {
+                return Err(TransacError::SignatureInvalid.into());
+            }
+            record.save(&accounts[0])?;
+            msg!("Signature verified");
         }
-        _ => return Err(ProgramError::InvalidInstructionData)
     }
 
     Ok(())
 }
 
+/// Creates one record holding `signature`. `accounts` are the record, its
+/// owner and the system program, in that order. Shared by all the create
+/// instructions.
+///
+/// Returns the record's address followed by its bump seed, as the create
+/// instructions hand back through return data.
+fn create_record(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    signature: &[u8],
+    entropy: &[u8; SALT_LEN],
+    label: &str,
+    password: &[u8],
+    options: &CreateOptions,
+) -> Result<[u8; 33], ProgramError> {
+    if label.len() > MAX_SEED_LEN {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    validate_password_strength(password)?;
+
+    if !accounts[1].is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (record_address, bump) = derive_record_address(program_id, accounts[1].key, label.as_bytes());
+    if record_address != *accounts[0].key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    // The program allocates the record itself below, so any existing data
+    // means a record is already there.
+    if !accounts[0].data_is_empty() {
+        return Err(TransacError::AlreadyInitialized.into());
+    }
+
+    let salt = derive_salt(entropy, accounts[0].key);
+    let created_at = Clock::get()?.unix_timestamp;
+    // The record's own label doubles as the label of its first entry.
+    let mut record = SignatureRecord::create_signature(
+        *accounts[1].key,
+        label.to_string(),
+        signature,
+        password,
+        salt,
+        created_at,
+        options.recovery_secret.as_ref(),
+    )?;
+    // A window that has already closed, or never opens, would leave the
+    // record permanently unviewable.
+    let opens_at = options.unlocks_at.unwrap_or(created_at).max(created_at);
+    if matches!(options.expires_at, Some(expires_at) if expires_at <= opens_at) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    record.expires_at = options.expires_at;
+    record.unlocks_at = options.unlocks_at;
+
+    // Duplicates would let one signer count more than once.
+    let authorities = &options.authorities;
+    let distinct = authorities
+        .iter()
+        .enumerate()
+        .all(|(i, authority)| !authorities[..i].contains(authority));
+    if authorities.len() > MAX_AUTHORITIES || options.threshold as usize > authorities.len() || !distinct {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    record.authorities = authorities.clone();
+    record.threshold = options.threshold;
+    record.set_metadata(options.metadata.clone())?;
+    record.bump = bump;
+
+    let space = record.allocation_len()?;
+    let minimum_balance = Rent::get()?.minimum_balance(space);
+    let seeds: &[&[u8]] = &[RECORD_SEED, accounts[1].key.as_ref(), label.as_bytes(), &[bump]];
+    if accounts[0].lamports() == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                accounts[1].key,
+                accounts[0].key,
+                minimum_balance,
+                space as u64,
+                program_id,
+            ),
+            &[accounts[1].clone(), accounts[0].clone(), accounts[2].clone()],
+            &[seeds],
+        )?;
+    } else {
+        // Anyone can send lamports to the address ahead of time, which would
+        // make `CreateAccount` fail. Top the balance up and take the account
+        // over in steps instead.
+        let shortfall = minimum_balance.saturating_sub(accounts[0].lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(accounts[1].key, accounts[0].key, shortfall),
+                &[accounts[1].clone(), accounts[0].clone(), accounts[2].clone()],
+            )?;
+        }
+        let record_accounts = [accounts[0].clone(), accounts[2].clone()];
+        invoke_signed(&system_instruction::allocate(accounts[0].key, space as u64), &record_accounts, &[seeds])?;
+        invoke_signed(&system_instruction::assign(accounts[0].key, program_id), &record_accounts, &[seeds])?;
+    }
+
+    check_rent_exempt(&accounts[0])?;
+    record.save(&accounts[0])?;
+    TransacEvent::RecordCreated {
+        record: *accounts[0].key,
+        owner: *accounts[1].key,
+        timestamp: created_at,
+    }
+    .emit();
+
+    let mut created = [0u8; 33];
+    created[..32].copy_from_slice(record_address.as_ref());
+    created[32] = bump;
+    Ok(created)
+}
+
+/// What a correct password unlocks.
+struct Unlocked {
+    data_key: EncryptionKey,
+    /// Key the password's copy of `data_key` is wrapped under, needed to
+    /// replace the data key.
+    wrapping_key: EncryptionKey,
+}
+
+/// Shared by `View` and `ViewPrehashed`.
+fn view(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    label: &str,
+    nonce: u64,
+    credential: Credential,
+) -> ProgramResult {
+    if accounts.is_empty() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let mut record = load_record(&accounts[0], program_id)?;
+    record.check_view_nonce(nonce)?;
+    record.check_viewable(&Clock::get()?)?;
+    record.check_authorities(&accounts[1..])?;
+    let Some(key) = authenticate_credential(&accounts[0], &mut record, credential)? else {
+        return Ok(());
+    };
+    // Hand the signature back through return data; logs are public and
+    // permanent, so it must never be printed.
+    let signature = record.decrypt_signature(&key, label)?;
+    record.record_view()?;
+    record.save(&accounts[0])?;
+    set_return_data(&signature);
+    msg!("Authorized access");
+    Ok(())
+}
+
+/// Shared by `Verify` and `VerifyPrehashed`.
+fn verify(program_id: &Pubkey, accounts: &[AccountInfo], credential: Credential) -> ProgramResult {
+    if accounts.is_empty() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let mut record = load_record(&accounts[0], program_id)?;
+    // Only the outcome is returned: 1 if the password matched, 0 if not.
+    let verified = authenticate_credential(&accounts[0], &mut record, credential)?.is_some();
+    if verified {
+        record.save(&accounts[0])?;
+    }
+    set_return_data(&[verified as u8]);
+    Ok(())
+}
+
+/// Checks `password` against `record` for an operation the owner has already
+/// signed for, failing with [`TransacError::WrongPassword`] if it doesn't
+/// match.
+///
+/// The runtime discards account writes from a failed instruction, so the
+/// bumped counter is lost along with it. That is acceptable here because only
+/// the owner can get this far, and the owner can change the password anyway.
+/// On success a `PasswordVerified` event is logged and the caller owns saving
+/// the record, along with whatever else it changes.
+fn authenticate(
+    account: &AccountInfo,
+    record: &mut SignatureRecord,
+    password: &[u8],
+) -> Result<Unlocked, ProgramError> {
+    let clock = Clock::get()?;
+    let unlocked = record.unlock(Credential::Password(password), &clock)?;
+    TransacEvent::PasswordVerified {
+        record: *account.key,
+        timestamp: clock.unix_timestamp,
+    }
+    .emit();
+    Ok(unlocked)
+}
+
+/// Checks `credential` against `record` for an operation anyone may attempt.
+///
+/// Reporting a wrong password as an error would also discard the bumped
+/// counter, leaving these paths open to unlimited guessing. Instead it is
+/// saved, logged, and returned as `Ok(None)`; callers then end the
+/// instruction without performing the operation. On success a
+/// `PasswordVerified` event is logged and the caller owns saving the record,
+/// along with whatever else it changes.
+///
+/// The flip side is that anyone can lock a record: [`MAX_FAILED_ATTEMPTS`]
+/// wrong guesses, which need no signature from the owner, shut out even the
+/// correct password for [`LOCKOUT_SLOTS`]. The owner keeps the operations
+/// that fail on a wrong password instead, since they never count towards a
+/// lockout, but is refused by these while one lasts, and a determined
+/// attacker can renew it indefinitely. Records that mustn't be lockable this
+/// way should require authorities co-signing views, see
+/// [`SignatureRecord::check_authorities`], which is checked first.
+fn authenticate_credential(
+    account: &AccountInfo,
+    record: &mut SignatureRecord,
+    credential: Credential,
+) -> Result<Option<EncryptionKey>, ProgramError> {
+    let clock = Clock::get()?;
+    match record.check_credential(credential, &clock) {
+        Ok(key) => {
+            TransacEvent::PasswordVerified {
+                record: *account.key,
+                timestamp: clock.unix_timestamp,
+            }
+            .emit();
+            Ok(Some(key))
+        }
+        Err(e) if e == TransacError::WrongPassword.into() => {
+            record.save(account)?;
+            msg!("Wrong password ({} failed attempts)", record.failed_attempts);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Enforces the policy for newly chosen passwords: at least
+/// [`MIN_PASSWORD_LEN`] bytes drawn from at least [`MIN_PASSWORD_CLASSES`]
+/// character classes. Passwords are raw bytes, so classes are judged on ASCII
+/// and any non-ASCII byte counts as "other".
+pub fn validate_password_strength(password: &[u8]) -> Result<(), ProgramError> {
+    let classes = [
+        password.iter().any(|b| b.is_ascii_lowercase()),
+        password.iter().any(|b| b.is_ascii_uppercase()),
+        password.iter().any(|b| b.is_ascii_digit()),
+        password.iter().any(|b| !b.is_ascii_alphanumeric()),
+    ];
+    let class_count = classes.iter().filter(|&&present| present).count();
+
+    if password.len() < MIN_PASSWORD_LEN || class_count < MIN_PASSWORD_CLASSES {
+        return Err(TransacError::WeakPassword.into());
+    }
+    Ok(())
+}
+
+/// Reads the signature to store from a source account's data. Signatures are
+/// opaque bytes and are stored as such.
+fn read_signature(source: &AccountInfo) -> Result<Vec<u8>, ProgramError> {
+    let data = source.data.borrow();
+    if data.len() > MAX_SIGNATURE_LEN {
+        return Err(TransacError::SignatureTooLong.into());
+    }
+    Ok(data.to_vec())
+}
+
+/// Grows `record` to `new_len` bytes, topping it up from `payer` so it stays
+/// rent-exempt. A single instruction may only grow an account by
+/// `MAX_PERMITTED_DATA_INCREASE` bytes.
+fn resize_record<'a>(
+    record: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_len: usize,
+) -> ProgramResult {
+    let current_len = record.data_len();
+    if new_len <= current_len {
+        return Ok(());
+    }
+    if new_len - current_len > MAX_PERMITTED_DATA_INCREASE {
+        msg!("Record cannot grow by more than {} bytes at once", MAX_PERMITTED_DATA_INCREASE);
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+
+    let shortfall = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(record.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, record.key, shortfall),
+            &[payer.clone(), record.clone(), system_program.clone()],
+        )?;
+    }
+    record.realloc(new_len, false)
+}
+
+/// Returns the canonical record address (and its bump) for `owner`'s record
+/// named `label`. `label` must be at most `MAX_SEED_LEN` bytes.
+pub fn derive_record_address(program_id: &Pubkey, owner: &Pubkey, label: &[u8]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECORD_SEED, owner.as_ref(), label], program_id)
+}
+
+/// Loads the record held in `account`, which must be owned by this program
+/// and sit at the address the record's seeds derive.
+fn load_record(account: &AccountInfo, program_id: &Pubkey) -> Result<SignatureRecord, ProgramError> {
+    check_owner(account, program_id)?;
+    let record = SignatureRecord::load(account)?;
+    record.check_address(account.key, program_id)?;
+    Ok(record)
+}
+
+/// Record accounts must be owned by this program, both so that we can write
+/// to them and so that their contents can't be forged by another program.
+fn check_owner(account: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    if account.owner != program_id {
+        return Err(ProgramError::IllegalOwner);
+    }
+    Ok(())
+}
+
+/// Records hold secrets nobody can recreate, so they must never sit in an
+/// account the runtime could reclaim. The program funds records itself when
+/// creating or growing them; this catches any path that left one short.
+fn check_rent_exempt(account: &AccountInfo) -> ProgramResult {
+    if !Rent::get()?.is_exempt(account.lamports(), account.data_len()) {
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    Ok(())
+}
+
+/// Drains `record` into `destination` and wipes its data so the runtime
+/// reclaims the account at the end of the transaction.
+fn close_account(record: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = record.lamports();
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **record.lamports.borrow_mut() = 0;
+
+    record.data.borrow_mut().fill(0);
+    record.realloc(0, false)
+}
+
+/// Decrypts a data key wrapped by [`SignatureRecord::wrap_data_key`].
+fn unwrap_data_key(
+    wrapped: &WrappedKey,
+    wrapping_key: &EncryptionKey,
+    associated_data: &[u8],
+) -> Result<EncryptionKey, ProgramError> {
+    let plaintext = decrypt(wrapping_key, &wrapped.nonce, associated_data, &wrapped.ciphertext)?;
+    let mut data_key = EncryptionKey::new([0; 32]);
+    if plaintext.len() != data_key.len() {
+        return Err(TransacError::DecryptionFailed.into());
+    }
+    data_key.copy_from_slice(&plaintext);
+    Ok(data_key)
+}
+
+/// Truncated SHA256 over a serialized record, catching partial writes and
+/// corruption before they surface as a confusing wrong-password result.
+fn record_checksum(record_bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&sha256(&[record_bytes])[..CHECKSUM_LEN]);
+    checksum
+}
+
 impl SignatureRecord {
-    pub fn create_signature(signature: String, password: &str) -> Self {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        let password_hash = hasher.finalize().into();
-        
-        SignatureRecord {
-            signature,
-            password_hash
+    /// Reads the record stored in `account`.
+    ///
+    /// The version byte is checked before anything else is parsed, since the
+    /// rest of the layout depends on it, and the checksum trailer is checked
+    /// before the record is handed out. No version is ever written as 0, so
+    /// a zeroed account is reported as uninitialized rather than parsed into
+    /// a record whose all-zero verifier could be matched. An account too small
+    /// to hold any record is refused before parsing is attempted.
+    pub fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
+        let data = account.data.borrow();
+        match data.first() {
+            Some(&RECORD_VERSION) => {}
+            None | Some(0) => return Err(TransacError::AccountNotInitialized.into()),
+            Some(_) => return Err(TransacError::UnsupportedVersion.into()),
         }
+        if data.len() < MIN_RECORD_LEN {
+            msg!("Record account holds {} bytes, a record needs at least {}", data.len(), MIN_RECORD_LEN);
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let mut rest = &data[..];
+        // Past a valid version byte, anything unparseable (a truncated field,
+        // a label that isn't UTF-8) is damage rather than a missing record.
+        let record = SignatureRecord::deserialize(&mut rest).map_err(|_| TransacError::CorruptRecord)?;
+        let body_len = data.len() - rest.len();
+        let stored = rest.get(..CHECKSUM_LEN).ok_or(TransacError::CorruptRecord)?;
+        if stored != record_checksum(&data[..body_len]) {
+            return Err(TransacError::CorruptRecord.into());
+        }
+        Ok(record)
     }
 
-    pub fn verify_and_view(&self, password: &str) -> Result<&str, ProgramError> {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        let attempt_hash = hasher.finalize();
+    /// Writes the record and its checksum into `account`, which must be large
+    /// enough to hold them.
+    pub fn save(&self, account: &AccountInfo) -> ProgramResult {
+        let mut bytes = borsh::to_vec(self).map_err(|_| ProgramError::InvalidAccountData)?;
+        let checksum = record_checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+
+        let mut data = account.data.borrow_mut();
+        if bytes.len() > data.len() {
+            msg!("Record needs {} bytes, account has {}", bytes.len(), data.len());
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        data[..bytes.len()].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Bytes to allocate for the record: its current size plus room for the
+    /// fields that can later grow without a payer at hand, namely a full
+    /// password history and a delegate grant.
+    pub fn allocation_len(&self) -> Result<usize, ProgramError> {
+        let history_room = (PASSWORD_HISTORY_LEN - self.password_history.len()) * 32;
+        let delegate_room = if self.delegate.is_none() { DELEGATE_LEN } else { 0 };
+        Ok(self.packed_len()? + history_room + delegate_room)
+    }
+
+    /// Bytes needed to store the record, checksum included.
+    pub fn packed_len(&self) -> Result<usize, ProgramError> {
+        get_instance_packed_len(self)
+            .map(|len| len + CHECKSUM_LEN)
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Builds a fresh record holding `signature` under `label`. `created_at`
+    /// is a unix timestamp; the processor takes it from `Clock::get`, so no
+    /// clock sysvar account is passed in. With a `recovery_secret`, the data
+    /// key is also wrapped under it so [`Self::recover`] can be used later.
+    pub fn create_signature(
+        owner: Pubkey,
+        label: String,
+        signature: &[u8],
+        password: &[u8],
+        salt: [u8; SALT_LEN],
+        created_at: i64,
+        recovery_secret: Option<&[u8; RECOVERY_SECRET_LEN]>,
+    ) -> Result<Self, ProgramError> {
+        let iterations = DEFAULT_PBKDF2_ITERATIONS;
+        let keys = derive_password_keys(password, &salt, iterations);
+
+        let mut record = SignatureRecord {
+            version: RECORD_VERSION,
+            owner,
+            signatures: Vec::new(),
+            next_nonce: 0,
+            salt,
+            iterations,
+            password_hash: keys.verifier,
+            // Wrapped below, once the record can hand out nonces.
+            data_key: WrappedKey {
+                nonce: [0; NONCE_LEN],
+                ciphertext: Vec::new(),
+            },
+            failed_attempts: 0,
+            locked_until_slot: 0,
+            created_at,
+            last_accessed_at: 0,
+            expires_at: None,
+            unlocks_at: None,
+            view_count: 0,
+            recovery: None,
+            delegate: None,
+            authorities: Vec::new(),
+            threshold: 0,
+            password_history: Vec::new(),
+            metadata: Vec::new(),
+            view_nonce: 0,
+            creator: owner,
+            seed_label: label.clone(),
+            // Only known once the address is derived, see `create_record`.
+            bump: 0,
+        };
+
+        let data_key = derive_data_key(&keys.wrapping_key, &record.take_nonce()?);
+        record.data_key = record.wrap_data_key(&data_key, &keys.wrapping_key, PASSWORD_WRAP_AAD)?;
+        if let Some(secret) = recovery_secret {
+            record.recovery = Some(Recovery {
+                recovery_key_hash: derive_recovery_verifier(secret),
+                public_key: derive_recovery_public_key(secret)?,
+                // Filled in by the wrap below.
+                ephemeral_key: [0; RECOVERY_POINT_LEN],
+                data_key: WrappedKey {
+                    nonce: [0; NONCE_LEN],
+                    ciphertext: Vec::new(),
+                },
+            });
+            record.wrap_recovery_key(&data_key)?;
+        }
+        record.append_signature(&data_key, label, signature)?;
+        Ok(record)
+    }
+
+    /// Checks `password` and `nonce` and returns the signature stored under
+    /// `label`.
+    pub fn verify_and_view(
+        &mut self,
+        password: &[u8],
+        label: &str,
+        nonce: u64,
+        clock: &Clock,
+    ) -> Result<Zeroizing<Vec<u8>>, ProgramError> {
+        self.check_view_nonce(nonce)?;
+        self.check_viewable(clock)?;
+        let key = self.check_password(password, clock)?;
+        let signature = self.decrypt_signature(&key, label)?;
+        self.record_view()?;
+        Ok(signature)
+    }
+
+    /// Refuses viewing outside the record's time window. Checked before the
+    /// password, so an expired record reveals nothing and burns no attempts.
+    pub fn check_viewable(&self, clock: &Clock) -> ProgramResult {
+        if matches!(self.unlocks_at, Some(unlocks_at) if clock.unix_timestamp < unlocks_at) {
+            return Err(TransacError::TimeLocked.into());
+        }
+        if matches!(self.expires_at, Some(expires_at) if clock.unix_timestamp > expires_at) {
+            return Err(TransacError::Expired.into());
+        }
+        Ok(())
+    }
+
+    /// Salt and PBKDF2 iteration count a client needs to pre-hash the
+    /// password, see [`Credential::PasswordKey`].
+    pub fn password_params(&self) -> ([u8; SALT_LEN], u32) {
+        (self.salt, self.iterations)
+    }
+
+    /// Bump seed of the record's address, which is the PDA for
+    /// `(creator, seed_label)`.
+    pub fn bump(&self) -> u8 {
+        self.bump
+    }
+
+    /// Requires `address` to be the one the record's stored seeds and bump
+    /// derive, so a record can't be passed in from anywhere else.
+    pub fn check_address(&self, address: &Pubkey, program_id: &Pubkey) -> ProgramResult {
+        let seeds: &[&[u8]] = &[RECORD_SEED, self.creator.as_ref(), self.seed_label.as_bytes(), &[self.bump]];
+        match Pubkey::create_program_address(seeds, program_id) {
+            Ok(derived) if derived == *address => Ok(()),
+            _ => Err(ProgramError::InvalidSeeds),
+        }
+    }
+
+    /// Nonce the next view has to carry.
+    pub fn view_nonce(&self) -> u64 {
+        self.view_nonce
+    }
+
+    /// Rejects a view carrying anything but the current view nonce, so a
+    /// replayed or reordered view can't succeed. Checked before the password
+    /// so stale views burn no attempts.
+    pub fn check_view_nonce(&self, nonce: u64) -> ProgramResult {
+        if nonce != self.view_nonce {
+            return Err(TransacError::StaleNonce.into());
+        }
+        Ok(())
+    }
+
+    /// Counts a successful view and moves on to the next view nonce.
+    fn record_view(&mut self) -> ProgramResult {
+        self.view_count = self.view_count.saturating_add(1);
+        self.view_nonce = self.view_nonce.checked_add(1).ok_or(TransacError::CounterOverflow)?;
+        Ok(())
+    }
 
-        if attempt_hash.as_slice() == &self.password_hash {
-            Ok(&self.signature)
+    /// Client annotation attached to the record.
+    pub fn metadata(&self) -> &[u8] {
+        &self.metadata
+    }
+
+    /// Replaces the metadata, which may be at most [`MAX_METADATA_LEN`] bytes.
+    pub fn set_metadata(&mut self, metadata: Vec<u8>) -> ProgramResult {
+        if metadata.len() > MAX_METADATA_LEN {
+            return Err(TransacError::MetadataTooLong.into());
+        }
+        self.metadata = metadata;
+        Ok(())
+    }
+
+    /// Requires at least `threshold` of the record's authorities to have
+    /// signed among `signers`. Checked before the password, like the time
+    /// window, so a view that can't succeed burns no attempts.
+    pub fn check_authorities(&self, signers: &[AccountInfo]) -> ProgramResult {
+        let signed = self
+            .authorities
+            .iter()
+            .filter(|authority| signers.iter().any(|a| a.is_signer && a.key == *authority))
+            .count();
+        if signed < self.threshold as usize {
+            return Err(TransacError::InsufficientSigners.into());
+        }
+        Ok(())
+    }
+
+    /// Checks `password` against the stored verifier and, if it matches,
+    /// returns the data key the signatures are encrypted under.
+    ///
+    /// Every wrong password counts towards [`MAX_FAILED_ATTEMPTS`]; reaching it
+    /// locks the record for [`LOCKOUT_SLOTS`] past the current slot, during
+    /// which even the correct password is refused. A correct password clears
+    /// the counter and stamps `last_accessed_at`. The caller must persist the
+    /// record for either to take effect.
+    ///
+    /// An all-zero verifier is never produced by a real password, only by
+    /// zeroed or forged data, so such a record is refused outright as
+    /// uninitialized. This backs up the version check in [`Self::load`].
+    pub fn check_password(&mut self, password: &[u8], clock: &Clock) -> Result<EncryptionKey, ProgramError> {
+        self.check_credential(Credential::Password(password), clock)
+    }
+
+    /// As [`Self::check_password`], for either form of credential. A password
+    /// key skips the PBKDF2 run, which is otherwise the bulk of the cost.
+    pub fn check_credential(
+        &mut self,
+        credential: Credential,
+        clock: &Clock,
+    ) -> Result<EncryptionKey, ProgramError> {
+        self.unlock(credential, clock).map(|unlocked| unlocked.data_key)
+    }
+
+    /// As [`Self::check_credential`], also returning the password's wrapping
+    /// key for operations that replace the data key.
+    fn unlock(&mut self, credential: Credential, clock: &Clock) -> Result<Unlocked, ProgramError> {
+        if self.password_hash == [0u8; 32] {
+            return Err(TransacError::AccountNotInitialized.into());
+        }
+        if clock.slot < self.locked_until_slot {
+            return Err(TransacError::Locked.into());
+        }
+
+        let keys = match credential {
+            Credential::Password(password) => derive_password_keys(password, &self.salt, self.iterations),
+            Credential::PasswordKey(password_key) => expand_password_key(password_key),
+        };
+
+        // Compare in constant time so the position of the first mismatching
+        // byte isn't observable.
+        if bool::from(keys.verifier.ct_eq(&self.password_hash)) {
+            self.failed_attempts = 0;
+            self.last_accessed_at = clock.unix_timestamp;
+            Ok(Unlocked {
+                data_key: unwrap_data_key(&self.data_key, &keys.wrapping_key, PASSWORD_WRAP_AAD)?,
+                wrapping_key: keys.wrapping_key,
+            })
         } else {
-            Err(ProgramError::InvalidArgument)
+            self.failed_attempts = self.failed_attempts.saturating_add(1);
+            if self.failed_attempts >= MAX_FAILED_ATTEMPTS {
+                self.failed_attempts = 0;
+                self.locked_until_slot = clock.slot.saturating_add(LOCKOUT_SLOTS);
+            }
+            Err(TransacError::WrongPassword.into())
+        }
+    }
+
+    /// Requires `signer` to be the record owner and to have signed. Checked
+    /// before the password so that only the owner can spend guesses through
+    /// operations that fail on a wrong password.
+    pub fn check_owner_signed(&self, signer: &AccountInfo) -> ProgramResult {
+        if *signer.key != self.owner {
+            return Err(TransacError::OwnerMismatch.into());
+        }
+        if !signer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// Decrypts the signature stored under `label`.
+    pub fn decrypt_signature(&self, key: &EncryptionKey, label: &str) -> Result<Zeroizing<Vec<u8>>, ProgramError> {
+        let entry = self
+            .signatures
+            .iter()
+            .find(|entry| entry.label == label)
+            .ok_or(TransacError::LabelNotFound)?;
+        decrypt(key, &entry.nonce, entry.label.as_bytes(), &entry.ciphertext)
+    }
+
+    /// Encrypts and adds a new labelled signature. Labels are unique within a
+    /// record, which holds at most [`MAX_LABELS`] of them and
+    /// [`MAX_TOTAL_SIGNATURE_LEN`] bytes in all.
+    pub fn append_signature(&mut self, key: &EncryptionKey, label: String, signature: &[u8]) -> ProgramResult {
+        if self.signatures.iter().any(|entry| entry.label == label) {
+            return Err(TransacError::DuplicateLabel.into());
+        }
+        if self.signatures.len() >= MAX_LABELS || self.signatures_len() + signature.len() > MAX_TOTAL_SIGNATURE_LEN {
+            return Err(TransacError::RecordFull.into());
+        }
+        let nonce = self.take_nonce()?;
+        let ciphertext = encrypt(key, &nonce, label.as_bytes(), signature)?;
+        self.signatures.push(SignatureEntry {
+            label,
+            nonce,
+            ciphertext,
+        });
+        Ok(())
+    }
+
+    /// Replaces the signature stored under `label`, re-encrypting it under a
+    /// fresh nonce. The password is left as it is.
+    pub fn rotate_signature(&mut self, key: &EncryptionKey, label: &str, signature: &[u8]) -> ProgramResult {
+        let index = self
+            .signatures
+            .iter()
+            .position(|entry| entry.label == label)
+            .ok_or(TransacError::LabelNotFound)?;
+        let stored_len = self.signatures_len() - self.signatures[index].plaintext_len();
+        if stored_len + signature.len() > MAX_TOTAL_SIGNATURE_LEN {
+            return Err(TransacError::RecordFull.into());
+        }
+        let nonce = self.take_nonce()?;
+        let entry = &mut self.signatures[index];
+        entry.ciphertext = encrypt(key, &nonce, entry.label.as_bytes(), signature)?;
+        entry.nonce = nonce;
+        Ok(())
+    }
+
+    /// Drops the signature stored under `label`. The account keeps its size,
+    /// so the space is reused by later appends.
+    pub fn remove_signature(&mut self, label: &str) -> ProgramResult {
+        let index = self
+            .signatures
+            .iter()
+            .position(|entry| entry.label == label)
+            .ok_or(TransacError::LabelNotFound)?;
+        self.signatures.remove(index);
+        Ok(())
+    }
+
+    /// Stored signature bytes across all labels, as counted against
+    /// [`MAX_TOTAL_SIGNATURE_LEN`].
+    fn signatures_len(&self) -> usize {
+        self.signatures.iter().map(SignatureEntry::plaintext_len).sum()
+    }
+
+    /// Sets `new_password`, keeping the existing salt but moving to the
+    /// current [`DEFAULT_PBKDF2_ITERATIONS`]. `data_key` is replaced, see
+    /// [`Self::replace_data_key`], so callers must have verified the current
+    /// password or recovery secret to obtain it. Anyone who learned the old
+    /// password can then read only what the account held before the change.
+    ///
+    /// The current password and those in the history are refused. Verifiers
+    /// are compared as stored, so one derived under an older iteration count
+    /// no longer matches.
+    pub fn set_password(&mut self, data_key: &EncryptionKey, new_password: &[u8]) -> ProgramResult {
+        let iterations = DEFAULT_PBKDF2_ITERATIONS;
+        let keys = derive_password_keys(new_password, &self.salt, iterations);
+        let reused = self
+            .password_history
+            .iter()
+            .chain(Some(&self.password_hash))
+            .any(|previous| bool::from(keys.verifier.ct_eq(previous)));
+        if reused {
+            return Err(TransacError::PasswordReused.into());
+        }
+
+        if self.password_history.len() == PASSWORD_HISTORY_LEN {
+            self.password_history.remove(0);
+        }
+        self.password_history.push(self.password_hash);
+        self.iterations = iterations;
+        self.password_hash = keys.verifier;
+        self.replace_data_key(data_key, &keys.wrapping_key)?;
+        Ok(())
+    }
+
+    /// Swaps `data_key` for a fresh one drawn from the password's
+    /// `wrapping_key`, re-encrypting every signature under it and re-wrapping
+    /// the password and recovery copies. A delegate's copy can't be re-wrapped
+    /// without its access key, so any grant is dropped. Returns the new key.
+    fn replace_data_key(
+        &mut self,
+        data_key: &EncryptionKey,
+        wrapping_key: &EncryptionKey,
+    ) -> Result<EncryptionKey, ProgramError> {
+        let new_key = derive_data_key(wrapping_key, &self.take_nonce()?);
+        for index in 0..self.signatures.len() {
+            let nonce = self.take_nonce()?;
+            let entry = &mut self.signatures[index];
+            let signature = decrypt(data_key, &entry.nonce, entry.label.as_bytes(), &entry.ciphertext)?;
+            entry.ciphertext = encrypt(&new_key, &nonce, entry.label.as_bytes(), &signature)?;
+            entry.nonce = nonce;
+        }
+        self.data_key = self.wrap_data_key(&new_key, wrapping_key, PASSWORD_WRAP_AAD)?;
+        self.wrap_recovery_key(&new_key)?;
+        self.delegate = None;
+        Ok(new_key)
+    }
+
+    /// Checks `secret` against the recovery key hash and, if it matches,
+    /// replaces the password with `new_password`. Any lockout is lifted: the
+    /// secret is a full-entropy key, so guessing it is not a concern, and a
+    /// locked-out owner is exactly who needs this.
+    pub fn recover(&mut self, secret: &[u8; RECOVERY_SECRET_LEN], new_password: &[u8]) -> ProgramResult {
+        let recovery = self.recovery.as_ref().ok_or(TransacError::RecoveryNotConfigured)?;
+        if !bool::from(derive_recovery_verifier(secret).ct_eq(&recovery.recovery_key_hash)) {
+            return Err(TransacError::WrongRecoveryKey.into());
+        }
+
+        let wrapping_key = derive_recovery_unwrap(secret, &recovery.ephemeral_key)?;
+        let data_key = unwrap_data_key(&recovery.data_key, &wrapping_key, RECOVERY_WRAP_AAD)?;
+        self.set_password(&data_key, new_password)?;
+        self.failed_attempts = 0;
+        self.locked_until_slot = 0;
+        Ok(())
+    }
+
+    /// Grants `grant.delegate` read access, wrapping the data key under the
+    /// grant's access key, or revokes any grant when `grant` is `None`. An
+    /// expiry that isn't after `now` is refused. Any earlier grant is revoked
+    /// first, see [`Self::revoke_delegate`]; `wrapping_key` is the
+    /// password's, as that needs.
+    pub fn set_delegate(
+        &mut self,
+        data_key: &EncryptionKey,
+        wrapping_key: &EncryptionKey,
+        grant: Option<&DelegateGrant>,
+        now: i64,
+    ) -> ProgramResult {
+        if matches!(grant, Some(grant) if grant.expires_at <= now) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let data_key = self.revoke_delegate(data_key, wrapping_key)?;
+        let Some(grant) = grant else {
+            return Ok(());
+        };
+        let access_wrapping_key = derive_access_wrapping_key(&grant.access_key);
+        self.delegate = Some(Delegate {
+            delegate: grant.delegate,
+            expires_at: grant.expires_at,
+            data_key: self.wrap_data_key(&data_key, &access_wrapping_key, DELEGATE_WRAP_AAD)?,
+        });
+        Ok(())
+    }
+
+    /// Drops any delegate and returns the data key that is current
+    /// afterwards.
+    ///
+    /// The account data is public, so a delegate holding the access key can
+    /// unwrap its copy of the data key offline, whatever the grant's expiry,
+    /// and keep it. Revoking therefore replaces the data key, see
+    /// [`Self::replace_data_key`], after which neither the access key nor a
+    /// data key taken from it decrypts anything the record holds. It can't
+    /// take back signatures the delegate has already read, and it only
+    /// happens on revocation: until then, an expired grant still lets the
+    /// delegate decrypt offline everything stored, even signatures added
+    /// after it expired. Changing the password or ownership drops the grant
+    /// the same way.
+    pub fn revoke_delegate(
+        &mut self,
+        data_key: &EncryptionKey,
+        wrapping_key: &EncryptionKey,
+    ) -> Result<EncryptionKey, ProgramError> {
+        if self.delegate.is_none() {
+            return Ok(data_key.clone());
+        }
+        self.replace_data_key(data_key, wrapping_key)
+    }
+
+    /// Returns the data key to the record's delegate, which must have signed
+    /// as `signer`, be within its grant, and present the grant's access key.
+    pub fn delegate_key(
+        &self,
+        signer: &AccountInfo,
+        access_key: &[u8; ACCESS_KEY_LEN],
+        clock: &Clock,
+    ) -> Result<EncryptionKey, ProgramError> {
+        let delegate = self
+            .delegate
+            .as_ref()
+            .filter(|delegate| delegate.delegate == *signer.key)
+            .ok_or(TransacError::NotDelegate)?;
+        if !signer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if clock.unix_timestamp >= delegate.expires_at {
+            return Err(TransacError::DelegationExpired.into());
+        }
+        let wrapping_key = derive_access_wrapping_key(access_key);
+        unwrap_data_key(&delegate.data_key, &wrapping_key, DELEGATE_WRAP_AAD)
+    }
+
+    /// Encrypts `data_key` under `wrapping_key` with a fresh nonce.
+    fn wrap_data_key(
+        &mut self,
+        data_key: &EncryptionKey,
+        wrapping_key: &EncryptionKey,
+        associated_data: &[u8],
+    ) -> Result<WrappedKey, ProgramError> {
+        let nonce = self.take_nonce()?;
+        let ciphertext = encrypt(wrapping_key, &nonce, associated_data, data_key.as_ref())?;
+        Ok(WrappedKey { nonce, ciphertext })
+    }
+
+    /// Wraps `data_key` to the recovery public key, if the record has one.
+    fn wrap_recovery_key(&mut self, data_key: &EncryptionKey) -> ProgramResult {
+        let Some(public_key) = self.recovery.as_ref().map(|recovery| recovery.public_key) else {
+            return Ok(());
+        };
+        let (ephemeral_key, wrapping_key) = derive_recovery_wrap(&public_key, data_key, &self.take_nonce()?)?;
+        let wrapped = self.wrap_data_key(data_key, &wrapping_key, RECOVERY_WRAP_AAD)?;
+        if let Some(recovery) = self.recovery.as_mut() {
+            recovery.ephemeral_key = ephemeral_key;
+            recovery.data_key = wrapped;
         }
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Hands out the next nonce. Nonces come from a counter that is never
+    /// reset, so no nonce is ever reused under a key.
+    fn take_nonce(&mut self) -> Result<[u8; NONCE_LEN], ProgramError> {
+        let counter = self.next_nonce;
+        self.next_nonce = counter.checked_add(1).ok_or(TransacError::CounterOverflow)?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..8].copy_from_slice(&counter.to_le_bytes());
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::system_program;
+
+    use super::*;
+    use crate::{
+        crypto::tests::sha256_units,
+        test_support::{MockAccount, MockOutcome, MockRuntime},
+    };
+
+    const PASSWORD: &[u8] = b"correct horse 1";
+    const NEW_PASSWORD: &[u8] = b"battery staple 2";
+    const RECOVERY_SECRET: [u8; RECOVERY_SECRET_LEN] = [9; RECOVERY_SECRET_LEN];
+    const LABEL: &str = "main";
+
+    // Positions of the accounts every fixture starts with.
+    const RECORD: usize = 0;
+    const OWNER: usize = 1;
+    const SYSTEM: usize = 2;
+
+    /// A program, its owner and, once created, one record, all held as mock
+    /// accounts that instructions are run against by index.
+    struct Fixture {
+        program_id: Pubkey,
+        runtime: MockRuntime,
+        accounts: Vec<MockAccount>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let program_id = Pubkey::new_unique();
+            let owner = Pubkey::new_unique();
+            let (record, _) = derive_record_address(&program_id, &owner, LABEL.as_bytes());
+            Fixture {
+                program_id,
+                runtime: MockRuntime::default(),
+                accounts: vec![
+                    MockAccount::new(record, system_program::id(), 0).writable(),
+                    MockAccount::new(owner, system_program::id(), 10_000_000_000).writable().signer(),
+                    MockAccount::new(system_program::id(), Pubkey::default(), 1),
+                ],
+            }
+        }
+
+        /// A fixture whose record holds `signature` under [`LABEL`].
+        fn created(signature: &[u8]) -> Self {
+            let mut fixture = Fixture::new();
+            fixture.create(signature, CreateOptions::default()).result.unwrap();
+            fixture
+        }
+
+        fn create(&mut self, signature: &[u8], options: CreateOptions) -> MockOutcome {
+            let create = TransacInstruction::CreateInline {
+                entropy: [7; SALT_LEN],
+                label: LABEL.to_string(),
+                signature: signature.to_vec(),
+                password: PASSWORD.to_vec(),
+                options,
+            };
+            self.run(&create, &[RECORD, OWNER, SYSTEM])
+        }
+
+        /// Adds an account and returns its index.
+        fn add(&mut self, account: MockAccount) -> usize {
+            self.accounts.push(account);
+            self.accounts.len() - 1
+        }
+
+        /// Adds a read-only account holding `data`, as a signature source.
+        fn add_source(&mut self, data: &[u8]) -> usize {
+            let mut source = MockAccount::new(Pubkey::new_unique(), system_program::id(), 1);
+            source.data = data.to_vec();
+            self.add(source)
+        }
+
+        /// Runs `instruction` with the accounts at `indices`, in that order.
+        fn run(&mut self, instruction: &TransacInstruction, indices: &[usize]) -> MockOutcome {
+            self.run_raw(&borsh::to_vec(instruction).unwrap(), indices)
+        }
+
+        fn run_raw(&mut self, instruction_data: &[u8], indices: &[usize]) -> MockOutcome {
+            let mut accounts: Vec<_> = indices.iter().map(|&i| self.accounts[i].clone()).collect();
+            let outcome = self.runtime.process(&self.program_id, &mut accounts, instruction_data);
+            for (&i, account) in indices.iter().zip(accounts) {
+                self.accounts[i] = account;
+            }
+            outcome
+        }
+
+        fn record(&mut self) -> SignatureRecord {
+            self.accounts[RECORD].load_record().unwrap()
+        }
+
+        /// Stores the record as `change` leaves it, checksum and all.
+        fn rewrite_record(&mut self, change: impl FnOnce(&mut SignatureRecord)) {
+            let mut record = self.record();
+            change(&mut record);
+            self.accounts[RECORD] = MockAccount::record(&self.program_id, &mut record, &self.runtime.rent).unwrap();
+        }
+
+        fn view(&mut self, label: &str, password: &[u8]) -> MockOutcome {
+            let nonce = self.record().view_nonce();
+            let view = TransacInstruction::View {
+                label: label.to_string(),
+                password: password.to_vec(),
+                nonce,
+            };
+            self.run(&view, &[RECORD])
+        }
+
+        fn append(&mut self, label: &str, signature: &[u8], signer: usize) -> MockOutcome {
+            let source = self.add_source(signature);
+            let append = TransacInstruction::AppendSignature {
+                label: label.to_string(),
+                password: PASSWORD.to_vec(),
+            };
+            self.run(&append, &[source, RECORD, signer, SYSTEM])
+        }
+
+        fn update_password(&mut self, current_password: &[u8], new_password: &[u8]) -> MockOutcome {
+            let update = TransacInstruction::UpdatePassword {
+                current_password: current_password.to_vec(),
+                new_password: new_password.to_vec(),
+            };
+            self.run(&update, &[RECORD, OWNER])
+        }
+
+        fn recover(&mut self, new_password: &[u8]) -> MockOutcome {
+            let recover = TransacInstruction::Recover {
+                recovery_secret: RECOVERY_SECRET,
+                new_password: new_password.to_vec(),
+            };
+            self.run(&recover, &[RECORD, OWNER])
+        }
+
+        /// The current data key, as the holder of `password` could unwrap it
+        /// from the public account data.
+        fn data_key(&mut self, password: &[u8]) -> EncryptionKey {
+            let clock = self.runtime.clock.clone();
+            self.record().check_password(password, &clock).unwrap()
+        }
+
+        /// Adds a delegate account and grants it access until `expires_at`.
+        fn delegate(&mut self, access_key: [u8; ACCESS_KEY_LEN], expires_at: i64) -> usize {
+            let delegate = self.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 1).signer());
+            let grant = DelegateGrant {
+                delegate: self.accounts[delegate].key,
+                expires_at,
+                access_key,
+            };
+            let set = TransacInstruction::SetDelegate {
+                password: PASSWORD.to_vec(),
+                delegate: Some(grant),
+            };
+            self.run(&set, &[RECORD, OWNER]).result.unwrap();
+            delegate
+        }
+
+        fn delegate_view(&mut self, delegate: usize, access_key: [u8; ACCESS_KEY_LEN]) -> MockOutcome {
+            let nonce = self.record().view_nonce();
+            let view = TransacInstruction::DelegateView {
+                label: LABEL.to_string(),
+                access_key,
+                nonce,
+            };
+            self.run(&view, &[RECORD, delegate])
+        }
+
+        /// The data key as a delegate holding `access_key` can unwrap it
+        /// offline, ignoring the grant's expiry.
+        fn delegate_data_key(&mut self, access_key: [u8; ACCESS_KEY_LEN]) -> EncryptionKey {
+            let delegate = self.record().delegate.unwrap();
+            unwrap_data_key(&delegate.data_key, &derive_access_wrapping_key(&access_key), DELEGATE_WRAP_AAD).unwrap()
+        }
+
+        fn verify(&mut self, password: &[u8]) -> MockOutcome {
+            let verify = TransacInstruction::Verify { password: password.to_vec() };
+            self.run(&verify, &[RECORD])
+        }
+
+        fn remove(&mut self, label: &str) -> MockOutcome {
+            let remove = TransacInstruction::RemoveSignature {
+                label: label.to_string(),
+                password: PASSWORD.to_vec(),
+            };
+            self.run(&remove, &[RECORD, OWNER])
+        }
+    }
+
+    fn err(error: TransacError) -> ProgramResult {
+        Err(error.into())
+    }
+
+    #[test]
+    fn labelled_signatures_can_be_added_viewed_and_removed() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.append("second", b"second signature", OWNER).result.unwrap();
+
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+        assert_eq!(fixture.view("second", PASSWORD).return_data.unwrap(), b"second signature");
+
+        fixture.remove(LABEL).result.unwrap();
+        assert_eq!(fixture.view(LABEL, PASSWORD).result, err(TransacError::LabelNotFound));
+        assert_eq!(fixture.view("second", PASSWORD).return_data.unwrap(), b"second signature");
+    }
+
+    #[test]
+    fn missing_labels_are_not_found() {
+        let mut fixture = Fixture::created(b"first");
+        assert_eq!(fixture.view("missing", PASSWORD).result, err(TransacError::LabelNotFound));
+        assert_eq!(fixture.remove("missing").result, err(TransacError::LabelNotFound));
+    }
+
+    #[test]
+    fn record_holds_at_most_max_labels() {
+        let mut fixture = Fixture::created(b"first");
+        for i in 1..MAX_LABELS {
+            fixture.append(&format!("label {i}"), b"sig", OWNER).result.unwrap();
+        }
+        assert_eq!(fixture.append("one more", b"sig", OWNER).result, err(TransacError::RecordFull));
+    }
+
+    #[test]
+    fn only_the_owner_can_append() {
+        let mut fixture = Fixture::created(b"first");
+        let stranger = MockAccount::new(Pubkey::new_unique(), system_program::id(), 1_000_000_000).signer();
+        let stranger = fixture.add(stranger);
+        assert_eq!(fixture.append("second", b"sig", stranger).result, err(TransacError::OwnerMismatch));
+    }
+
+    #[test]
+    fn verify_reports_the_outcome_in_return_data() {
+        let mut fixture = Fixture::created(b"first");
+        let verified = fixture.verify(PASSWORD);
+        assert_eq!(verified.result, Ok(()));
+        assert_eq!(verified.return_data.unwrap(), [1]);
+
+        let refused = fixture.verify(b"wrong horse 1");
+        assert_eq!(refused.result, Ok(()));
+        assert_eq!(refused.return_data.unwrap(), [0]);
+        assert_eq!(fixture.record().failed_attempts, 1);
+    }
+
+    #[test]
+    fn owner_operations_fail_on_a_wrong_password() {
+        let mut fixture = Fixture::created(b"first");
+        let wrong = b"wrong horse 1".to_vec();
+        let destination = fixture.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 0).writable());
+        let cases = [
+            (
+                TransacInstruction::UpdatePassword {
+                    current_password: wrong.clone(),
+                    new_password: b"battery staple 2".to_vec(),
+                },
+                vec![RECORD, OWNER],
+            ),
+            (TransacInstruction::Close { password: wrong.clone() }, vec![RECORD, destination, OWNER]),
+            (
+                TransacInstruction::TransferOwnership {
+                    password: wrong.clone(),
+                    new_owner: Pubkey::new_unique(),
+                },
+                vec![RECORD, OWNER],
+            ),
+            (
+                TransacInstruction::RemoveSignature {
+                    label: LABEL.to_string(),
+                    password: wrong.clone(),
+                },
+                vec![RECORD, OWNER],
+            ),
+            (TransacInstruction::SetDelegate { password: wrong.clone(), delegate: None }, vec![RECORD, OWNER]),
+        ];
+        for (instruction, accounts) in cases {
+            assert_eq!(fixture.run(&instruction, &accounts).result, err(TransacError::WrongPassword));
+        }
+
+        let source = fixture.add_source(b"second");
+        let rotate = TransacInstruction::RotateSignature {
+            label: LABEL.to_string(),
+            password: wrong,
+        };
+        assert_eq!(
+            fixture.run(&rotate, &[source, RECORD, OWNER, SYSTEM]).result,
+            err(TransacError::WrongPassword)
+        );
+        // Nothing is saved on the way out, and the runtime would discard it
+        // anyway, so none of these count.
+        assert_eq!(fixture.record().failed_attempts, 0);
+    }
+
+    #[test]
+    fn repeated_wrong_passwords_lock_the_record() {
+        let mut fixture = Fixture::created(b"first");
+        for attempts in 1..MAX_FAILED_ATTEMPTS {
+            assert_eq!(fixture.view(LABEL, b"wrong horse 1").return_data, None);
+            assert_eq!(fixture.record().failed_attempts, attempts);
+        }
+        assert_eq!(fixture.verify(b"wrong horse 1").return_data.unwrap(), [0]);
+
+        // The counter restarts once the lockout is set.
+        let record = fixture.record();
+        assert_eq!(record.failed_attempts, 0);
+        assert_eq!(record.locked_until_slot, fixture.runtime.clock.slot + LOCKOUT_SLOTS);
+        assert_eq!(fixture.verify(PASSWORD).result, err(TransacError::Locked));
+        assert_eq!(fixture.view(LABEL, PASSWORD).result, err(TransacError::Locked));
+
+        fixture.runtime.clock.slot += LOCKOUT_SLOTS;
+        assert_eq!(fixture.verify(PASSWORD).return_data.unwrap(), [1]);
+    }
+
+    #[test]
+    fn correct_password_resets_failed_attempts() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.verify(b"wrong horse 1");
+        fixture.verify(b"wrong horse 1");
+        assert_eq!(fixture.record().failed_attempts, 2);
+
+        fixture.verify(PASSWORD);
+        assert_eq!(fixture.record().failed_attempts, 0);
+    }
+
+    #[test]
+    fn password_change_replaces_the_data_key() {
+        let options = CreateOptions {
+            recovery_secret: Some(RECOVERY_SECRET),
+            ..CreateOptions::default()
+        };
+        let mut fixture = Fixture::new();
+        fixture.create(b"first", options).result.unwrap();
+        fixture.append("second", b"second signature", OWNER).result.unwrap();
+        let old_key = fixture.data_key(PASSWORD);
+
+        fixture.update_password(PASSWORD, NEW_PASSWORD).result.unwrap();
+        let record = fixture.record();
+        for label in [LABEL, "second"] {
+            assert_eq!(record.decrypt_signature(&old_key, label).unwrap_err(), TransacError::DecryptionFailed.into());
+        }
+        assert_eq!(fixture.verify(PASSWORD).return_data.unwrap(), [0]);
+        assert_eq!(fixture.view(LABEL, NEW_PASSWORD).return_data.unwrap(), b"first");
+        assert_eq!(fixture.view("second", NEW_PASSWORD).return_data.unwrap(), b"second signature");
+
+        // The recovery copy follows the new key, so recovery still works.
+        fixture.recover(b"third password 3").result.unwrap();
+        assert_ne!(*fixture.data_key(b"third password 3"), *old_key);
+        assert_eq!(fixture.view("second", b"third password 3").return_data.unwrap(), b"second signature");
+    }
+
+    #[test]
+    fn revoking_a_delegate_replaces_the_data_key() {
+        let mut fixture = Fixture::created(b"first");
+        let now = fixture.runtime.clock.unix_timestamp;
+        let access_key = [1; ACCESS_KEY_LEN];
+        let delegate = fixture.delegate(access_key, now + 60);
+        assert_eq!(fixture.delegate_view(delegate, access_key).return_data.unwrap(), b"first");
+
+        // Past the expiry the program refuses, but the copy still unwraps.
+        fixture.runtime.clock.unix_timestamp = now + 60;
+        assert_eq!(fixture.delegate_view(delegate, access_key).result, err(TransacError::DelegationExpired));
+        let leaked_key = fixture.delegate_data_key(access_key);
+        assert!(fixture.record().decrypt_signature(&leaked_key, LABEL).is_ok());
+
+        let revoke = TransacInstruction::SetDelegate {
+            password: PASSWORD.to_vec(),
+            delegate: None,
+        };
+        fixture.run(&revoke, &[RECORD, OWNER]).result.unwrap();
+        let record = fixture.record();
+        assert!(record.delegate.is_none());
+        assert_eq!(record.decrypt_signature(&leaked_key, LABEL).unwrap_err(), TransacError::DecryptionFailed.into());
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn replacing_a_delegate_cuts_off_the_previous_one() {
+        let mut fixture = Fixture::created(b"first");
+        let expires_at = fixture.runtime.clock.unix_timestamp + 60;
+        fixture.delegate([1; ACCESS_KEY_LEN], expires_at);
+        let leaked_key = fixture.delegate_data_key([1; ACCESS_KEY_LEN]);
+
+        let delegate = fixture.delegate([2; ACCESS_KEY_LEN], expires_at);
+        assert_eq!(fixture.delegate_view(delegate, [2; ACCESS_KEY_LEN]).return_data.unwrap(), b"first");
+        let record = fixture.record();
+        assert_eq!(record.decrypt_signature(&leaked_key, LABEL).unwrap_err(), TransacError::DecryptionFailed.into());
+    }
+
+    #[test]
+    fn password_change_and_transfer_drop_the_delegate() {
+        let mut fixture = Fixture::created(b"first");
+        let expires_at = fixture.runtime.clock.unix_timestamp + 60;
+        fixture.delegate([1; ACCESS_KEY_LEN], expires_at);
+        let leaked_key = fixture.delegate_data_key([1; ACCESS_KEY_LEN]);
+        fixture.update_password(PASSWORD, NEW_PASSWORD).result.unwrap();
+        let record = fixture.record();
+        assert!(record.delegate.is_none());
+        assert!(record.decrypt_signature(&leaked_key, LABEL).is_err());
+
+        let mut fixture = Fixture::created(b"first");
+        fixture.delegate([1; ACCESS_KEY_LEN], expires_at);
+        let leaked_key = fixture.delegate_data_key([1; ACCESS_KEY_LEN]);
+        let transfer = TransacInstruction::TransferOwnership {
+            password: PASSWORD.to_vec(),
+            new_owner: Pubkey::new_unique(),
+        };
+        fixture.run(&transfer, &[RECORD, OWNER]).result.unwrap();
+        let record = fixture.record();
+        assert!(record.delegate.is_none());
+        assert!(record.decrypt_signature(&leaked_key, LABEL).is_err());
+    }
+
+    #[test]
+    fn create_takes_over_a_prefunded_address() {
+        for lamports in [1, 1_000_000_000] {
+            let mut fixture = Fixture::new();
+            fixture.accounts[RECORD].lamports = lamports;
+            fixture.create(b"first", CreateOptions::default()).result.unwrap();
+
+            let record = &fixture.accounts[RECORD];
+            assert_eq!(record.owner, fixture.program_id);
+            assert!(fixture.runtime.rent.is_exempt(record.lamports, record.data.len()));
+            assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+        }
+    }
+
+    #[test]
+    fn create_requires_the_record_address() {
+        let mut fixture = Fixture::new();
+        let (other_label, _) = derive_record_address(&fixture.program_id, &fixture.accounts[OWNER].key, b"other");
+        fixture.accounts[RECORD].key = other_label;
+        assert_eq!(fixture.create(b"first", CreateOptions::default()).result, Err(ProgramError::InvalidSeeds));
+
+        fixture.accounts[RECORD].key = Pubkey::new_unique();
+        assert_eq!(fixture.create(b"first", CreateOptions::default()).result, Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn oversized_fields_are_refused_before_hashing() {
+        let mut fixture = Fixture::created(b"first");
+        let longest = vec![b'a'; MAX_PASSWORD_LEN];
+        assert_eq!(fixture.verify(&longest).return_data.unwrap(), [0]);
+
+        let outcome = fixture.verify(&[b'a'; MAX_PASSWORD_LEN + 1]);
+        assert_eq!(outcome.result, err(TransacError::InputTooLong));
+        assert!(outcome.logs.iter().any(|log| log == "Password exceeds 128 bytes"));
+        // Refused before the password was checked, so only the first counts.
+        assert_eq!(fixture.record().failed_attempts, 1);
+
+        let verify_ed25519 = TransacInstruction::VerifyEd25519 {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            message: vec![0; MAX_MESSAGE_LEN + 1],
+            signer: Pubkey::new_unique(),
+        };
+        assert_eq!(fixture.run(&verify_ed25519, &[RECORD]).result, err(TransacError::InputTooLong));
+
+        let verify_and_call = TransacInstruction::VerifyAndCall {
+            password: PASSWORD.to_vec(),
+            data: vec![0; MAX_CALLBACK_DATA_LEN + 1],
+        };
+        assert_eq!(fixture.run(&verify_and_call, &[RECORD]).result, err(TransacError::InputTooLong));
+    }
+
+    #[test]
+    fn create_inline_needs_no_source_account() {
+        let mut fixture = Fixture::new();
+        let outcome = fixture.create(b"inline signature", CreateOptions::default());
+        let owner = fixture.accounts[OWNER].key;
+        let (address, bump) = derive_record_address(&fixture.program_id, &owner, LABEL.as_bytes());
+        let mut created = address.to_bytes().to_vec();
+        created.push(bump);
+        assert_eq!(outcome.return_data.unwrap(), created);
+
+        assert_eq!(fixture.verify(PASSWORD).return_data.unwrap(), [1]);
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"inline signature");
+    }
+
+    #[test]
+    fn create_inline_caps_its_fields_together() {
+        let signature = vec![1; MAX_INLINE_LEN - PASSWORD.len() - LABEL.len()];
+        let mut fixture = Fixture::new();
+        fixture.create(&signature, CreateOptions::default()).result.unwrap();
+
+        // Each field is within its own limit, but not all of them together.
+        let options = CreateOptions {
+            metadata: b"x".to_vec(),
+            ..CreateOptions::default()
+        };
+        let mut fixture = Fixture::new();
+        assert_eq!(fixture.create(&signature, options).result, err(TransacError::InputTooLong));
+    }
+
+    /// Callback for `VerifyAndCall` tests: copies its data into its one
+    /// account.
+    fn record_callback(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+        accounts[0].try_borrow_mut_data()?.copy_from_slice(data);
+        Ok(())
+    }
+
+    #[test]
+    fn verify_and_call_invokes_only_on_a_correct_password() {
+        let mut fixture = Fixture::created(b"first");
+        let callback_id = Pubkey::new_unique();
+        fixture.runtime.programs.push((callback_id, record_callback));
+        let mut callback = MockAccount::new(callback_id, Pubkey::default(), 1);
+        callback.executable = true;
+        let callback = fixture.add(callback);
+        let mut target = MockAccount::new(Pubkey::new_unique(), callback_id, 1).writable();
+        target.data = vec![0; 6];
+        let target = fixture.add(target);
+
+        let call = |password: &[u8]| TransacInstruction::VerifyAndCall {
+            password: password.to_vec(),
+            data: b"called".to_vec(),
+        };
+        let outcome = fixture.run(&call(b"wrong horse 1"), &[RECORD, callback, target]);
+        assert_eq!(outcome.result, Ok(()));
+        assert_eq!(fixture.accounts[target].data, [0; 6]);
+        assert_eq!(fixture.record().failed_attempts, 1);
+
+        let outcome = fixture.run(&call(PASSWORD), &[RECORD, callback, target]);
+        assert_eq!(outcome.result, Ok(()));
+        assert!(outcome.logs.iter().any(|log| log == "Callback invoked"));
+        assert_eq!(fixture.accounts[target].data, b"called");
+    }
+
+    #[test]
+    fn verify_and_call_refuses_unusable_callbacks() {
+        let mut fixture = Fixture::created(b"first");
+        let call = TransacInstruction::VerifyAndCall {
+            password: PASSWORD.to_vec(),
+            data: Vec::new(),
+        };
+        let not_executable = fixture.add(MockAccount::new(Pubkey::new_unique(), Pubkey::default(), 1));
+        assert_eq!(fixture.run(&call, &[RECORD, not_executable]).result, Err(ProgramError::IncorrectProgramId));
+
+        let mut this_program = MockAccount::new(fixture.program_id, Pubkey::default(), 1);
+        this_program.executable = true;
+        let this_program = fixture.add(this_program);
+        assert_eq!(fixture.run(&call, &[RECORD, this_program]).result, Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn check_password_accepts_only_the_password() {
+        let mut fixture = Fixture::created(b"first");
+        let clock = fixture.runtime.clock.clone();
+        let mut record = fixture.record();
+        assert!(record.check_password(PASSWORD, &clock).is_ok());
+        // Differs from the password in its last byte only.
+        let near_miss = b"correct horse 2";
+        assert_eq!(record.check_password(near_miss, &clock).unwrap_err(), TransacError::WrongPassword.into());
+        assert_eq!(record.check_password(b"", &clock).unwrap_err(), TransacError::WrongPassword.into());
+    }
+
+    #[test]
+    fn empty_instruction_data_is_refused() {
+        let mut fixture = Fixture::created(b"first");
+        let outcome = fixture.run_raw(&[], &[RECORD]);
+        assert_eq!(outcome.result, Err(ProgramError::InvalidInstructionData));
+        assert!(outcome.logs.iter().any(|log| log == "Instruction data is empty"));
+    }
+
+    #[test]
+    fn every_instruction_needs_its_accounts() {
+        let mut fixture = Fixture::new();
+        for instruction in crate::instruction::tests::every_variant() {
+            let outcome = fixture.run(&instruction, &[]);
+            assert_eq!(outcome.result, Err(ProgramError::NotEnoughAccountKeys), "{:?}", instruction.opcode());
+        }
+    }
+
+    #[test]
+    fn update_password_swaps_which_password_verifies() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.update_password(PASSWORD, NEW_PASSWORD).result.unwrap();
+        assert_eq!(fixture.verify(PASSWORD).return_data.unwrap(), [0]);
+        assert_eq!(fixture.verify(NEW_PASSWORD).return_data.unwrap(), [1]);
+
+        // The old password no longer counts as the current one either.
+        let outcome = fixture.update_password(PASSWORD, b"third password 3");
+        assert_eq!(outcome.result, err(TransacError::WrongPassword));
+    }
+
+    #[test]
+    fn close_pays_the_record_lamports_to_the_destination() {
+        let mut fixture = Fixture::created(b"first");
+        let destination = fixture.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 5).writable());
+        let lamports = fixture.accounts[RECORD].lamports;
+        let close = TransacInstruction::Close {
+            password: PASSWORD.to_vec(),
+        };
+        fixture.run(&close, &[RECORD, destination, OWNER]).result.unwrap();
+
+        assert_eq!(fixture.accounts[destination].lamports, lamports + 5);
+        assert_eq!(fixture.accounts[RECORD].lamports, 0);
+        assert!(fixture.accounts[RECORD].data.is_empty());
+        assert_eq!(fixture.verify(PASSWORD).result, err(TransacError::AccountNotInitialized));
+    }
+
+    #[test]
+    fn a_second_create_is_refused() {
+        let mut fixture = Fixture::created(b"first");
+        let outcome = fixture.create(b"second", CreateOptions::default());
+        assert_eq!(outcome.result, err(TransacError::AlreadyInitialized));
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn records_owned_by_another_program_are_refused() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.accounts[RECORD].owner = Pubkey::new_unique();
+        assert_eq!(fixture.verify(PASSWORD).result, Err(ProgramError::IllegalOwner));
+        assert_eq!(fixture.update_password(PASSWORD, NEW_PASSWORD).result, Err(ProgramError::IllegalOwner));
+    }
+
+    #[test]
+    fn the_owner_must_sign() {
+        let mut fixture = Fixture::new();
+        fixture.accounts[OWNER].is_signer = false;
+        let outcome = fixture.create(b"first", CreateOptions::default());
+        assert_eq!(outcome.result, Err(ProgramError::MissingRequiredSignature));
+
+        fixture.accounts[OWNER].is_signer = true;
+        fixture.create(b"first", CreateOptions::default()).result.unwrap();
+        fixture.accounts[OWNER].is_signer = false;
+        let outcome = fixture.update_password(PASSWORD, NEW_PASSWORD);
+        assert_eq!(outcome.result, Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn records_round_trip_through_borsh() {
+        let options = CreateOptions {
+            expires_at: Some(i64::MAX),
+            recovery_secret: Some(RECOVERY_SECRET),
+            authorities: vec![Pubkey::new_unique()],
+            metadata: b"note".to_vec(),
+            ..CreateOptions::default()
+        };
+        let mut fixture = Fixture::new();
+        fixture.create(b"first", options).result.unwrap();
+        let bytes = borsh::to_vec(&fixture.record()).unwrap();
+        assert_eq!(fixture.accounts[RECORD].data[..bytes.len()], bytes);
+        assert_eq!(fixture.accounts[RECORD].data[bytes.len()..][..CHECKSUM_LEN], record_checksum(&bytes));
+
+        let decoded = SignatureRecord::try_from_slice(&bytes).unwrap();
+        assert_eq!(borsh::to_vec(&decoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn unknown_record_versions_are_refused() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.accounts[RECORD].data[0] = RECORD_VERSION + 1;
+        assert_eq!(fixture.verify(PASSWORD).result, err(TransacError::UnsupportedVersion));
+        assert_eq!(fixture.accounts[RECORD].load_record().unwrap_err(), TransacError::UnsupportedVersion.into());
+    }
+
+    #[test]
+    fn signatures_are_looked_up_by_label() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.append("second", b"second signature", OWNER).result.unwrap();
+        fixture.append("third", b"third signature", OWNER).result.unwrap();
+        let expected: [(&str, &[u8]); 3] =
+            [("third", b"third signature"), (LABEL, b"first"), ("second", b"second signature")];
+        for (label, signature) in expected {
+            assert_eq!(fixture.view(label, PASSWORD).return_data.unwrap(), signature);
+        }
+
+        // Each label is bound into its entry's encryption, so entries can't be
+        // swapped to serve one signature under another label.
+        let key = fixture.data_key(PASSWORD);
+        let mut record = fixture.record();
+        let first_label = record.signatures[0].label.clone();
+        record.signatures[0].label = record.signatures[1].label.clone();
+        record.signatures[1].label = first_label;
+        assert_eq!(record.decrypt_signature(&key, LABEL).unwrap_err(), TransacError::DecryptionFailed.into());
+    }
+
+    #[test]
+    fn timestamps_follow_the_clock() {
+        let mut fixture = Fixture::new();
+        fixture.runtime.clock.unix_timestamp = 1_000;
+        fixture.create(b"first", CreateOptions::default()).result.unwrap();
+        let record = fixture.record();
+        assert_eq!((record.created_at, record.last_accessed_at), (1_000, 0));
+
+        fixture.runtime.clock.unix_timestamp = 2_000;
+        fixture.view(LABEL, PASSWORD).result.unwrap();
+        fixture.runtime.clock.unix_timestamp = 3_000;
+        fixture.view(LABEL, b"wrong horse 1").result.unwrap();
+        let record = fixture.record();
+        assert_eq!((record.created_at, record.last_accessed_at), (1_000, 2_000));
+    }
+
+    #[test]
+    fn views_return_the_signature_without_logging_it() {
+        let signature = b"very secret signature";
+        let mut fixture = Fixture::created(signature);
+        let outcome = fixture.view(LABEL, PASSWORD);
+        assert_eq!(outcome.return_data.unwrap(), signature);
+
+        let contains = |haystack: &[u8]| haystack.windows(signature.len()).any(|window| window == signature);
+        assert!(!outcome.logs.iter().any(|log| contains(log.as_bytes())));
+        assert!(!outcome.data_logs.iter().flatten().any(|field| contains(field)));
+    }
+
+    #[test]
+    fn verify_reveals_nothing_but_the_outcome() {
+        let mut fixture = Fixture::created(b"first");
+        for (password, expected) in [(PASSWORD, 1), (&b"wrong horse 1"[..], 0)] {
+            let outcome = fixture.verify(password);
+            assert_eq!(outcome.return_data.unwrap(), [expected]);
+            assert!(!outcome.logs.iter().any(|log| log.contains("first")));
+        }
+        // Verifying is not viewing.
+        let record = fixture.record();
+        assert_eq!((record.view_count, record.view_nonce), (0, 0));
+    }
+
+    #[test]
+    fn signatures_are_capped_at_max_signature_len() {
+        let create = TransacInstruction::Create {
+            entropy: [7; SALT_LEN],
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            options: CreateOptions::default(),
+        };
+        let mut fixture = Fixture::new();
+        let source = fixture.add_source(&[1; MAX_SIGNATURE_LEN + 1]);
+        let outcome = fixture.run(&create, &[source, RECORD, OWNER, SYSTEM]);
+        assert_eq!(outcome.result, err(TransacError::SignatureTooLong));
+
+        let source = fixture.add_source(&[1; MAX_SIGNATURE_LEN]);
+        fixture.run(&create, &[source, RECORD, OWNER, SYSTEM]).result.unwrap();
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), [1; MAX_SIGNATURE_LEN]);
+    }
+
+    #[test]
+    fn signature_storage_cost() {
+        let create = TransacInstruction::Create {
+            entropy: [7; SALT_LEN],
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            options: CreateOptions::default(),
+        };
+        let units = |signature: &[u8]| {
+            let mut fixture = Fixture::new();
+            let source = fixture.add_source(signature);
+            fixture.run(&create, &[source, RECORD, OWNER, SYSTEM]).result.unwrap();
+            let verify = sha256_units(|| fixture.verify(PASSWORD).result.unwrap());
+            let view = sha256_units(|| fixture.view(LABEL, PASSWORD).result.unwrap());
+            (verify, view)
+        };
+        // PBKDF2 accounts for 70,650 of each; the rest is the record checksum,
+        // which hashes the whole record twice per verify and three times per
+        // view. Entries are length-prefixed, so a short signature is hashed as
+        // short. A fixed MAX_SIGNATURE_LEN slot would charge every record the
+        // second figure, about 511 units more per pass.
+        assert_eq!(units(b"sig"), (71_118, 71_352));
+        assert_eq!(units(&[1; MAX_SIGNATURE_LEN]), (72_140, 72_885));
+    }
+
+    #[test]
+    fn records_must_stay_rent_exempt() {
+        let mut fixture = Fixture::new();
+        fixture.accounts[OWNER].lamports = 1_000;
+        let outcome = fixture.create(b"first", CreateOptions::default());
+        assert_eq!(outcome.result, Err(ProgramError::InsufficientFunds));
+
+        // Growing the record tops it up; otherwise an under-funded record is
+        // refused rather than left to the runtime.
+        let mut fixture = Fixture::created(b"first");
+        let set_metadata = |metadata: &[u8]| TransacInstruction::SetMetadata {
+            metadata: metadata.to_vec(),
+        };
+        fixture.accounts[RECORD].lamports = 1;
+        fixture.run(&set_metadata(b"note"), &[RECORD, OWNER, SYSTEM]).result.unwrap();
+        let record = &fixture.accounts[RECORD];
+        assert!(fixture.runtime.rent.is_exempt(record.lamports, record.data.len()));
+
+        fixture.accounts[RECORD].lamports = 1;
+        let outcome = fixture.run(&set_metadata(b"memo"), &[RECORD, OWNER, SYSTEM]);
+        assert_eq!(outcome.result, Err(ProgramError::AccountNotRentExempt));
+    }
+
+    #[test]
+    fn weak_passwords_are_refused() {
+        let weak = TransacError::WeakPassword.into();
+        assert_eq!(validate_password_strength(b"ab1!").unwrap_err(), weak);
+        assert_eq!(validate_password_strength(b"lowercaseonly").unwrap_err(), weak);
+        assert!(validate_password_strength(b"abcdefg1").is_ok());
+        assert!(validate_password_strength("pässwort".as_bytes()).is_ok());
+
+        let mut fixture = Fixture::new();
+        let create = TransacInstruction::CreateInline {
+            entropy: [7; SALT_LEN],
+            label: LABEL.to_string(),
+            signature: b"first".to_vec(),
+            password: b"short1".to_vec(),
+            options: CreateOptions::default(),
+        };
+        assert_eq!(fixture.run(&create, &[RECORD, OWNER, SYSTEM]).result, err(TransacError::WeakPassword));
+        fixture.create(b"first", CreateOptions::default()).result.unwrap();
+        assert_eq!(fixture.update_password(PASSWORD, b"short1").result, err(TransacError::WeakPassword));
+    }
+
+    #[test]
+    fn destructive_operations_need_the_owner_even_with_the_password() {
+        let mut fixture = Fixture::created(b"first");
+        let stranger = MockAccount::new(Pubkey::new_unique(), system_program::id(), 1_000_000_000).signer();
+        let stranger = fixture.add(stranger);
+        let destination = fixture.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 0).writable());
+        let password = PASSWORD.to_vec();
+        let cases = [
+            (
+                TransacInstruction::UpdatePassword {
+                    current_password: password.clone(),
+                    new_password: NEW_PASSWORD.to_vec(),
+                },
+                vec![RECORD, stranger],
+            ),
+            (TransacInstruction::Close { password: password.clone() }, vec![RECORD, destination, stranger]),
+            (
+                TransacInstruction::TransferOwnership {
+                    password: password.clone(),
+                    new_owner: fixture.accounts[stranger].key,
+                },
+                vec![RECORD, stranger],
+            ),
+            (
+                TransacInstruction::RemoveSignature {
+                    label: LABEL.to_string(),
+                    password: password.clone(),
+                },
+                vec![RECORD, stranger],
+            ),
+            (TransacInstruction::SetDelegate { password, delegate: None }, vec![RECORD, stranger]),
+        ];
+        for (instruction, accounts) in cases {
+            assert_eq!(fixture.run(&instruction, &accounts).result, err(TransacError::OwnerMismatch));
+        }
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn transfer_hands_the_record_to_the_new_owner() {
+        let mut fixture = Fixture::created(b"first");
+        let new_owner = MockAccount::new(Pubkey::new_unique(), system_program::id(), 1_000_000_000).writable().signer();
+        let new_owner = fixture.add(new_owner);
+        let transfer = TransacInstruction::TransferOwnership {
+            password: PASSWORD.to_vec(),
+            new_owner: fixture.accounts[new_owner].key,
+        };
+        fixture.run(&transfer, &[RECORD, OWNER]).result.unwrap();
+
+        assert_eq!(fixture.append("second", b"sig", OWNER).result, err(TransacError::OwnerMismatch));
+        fixture.append("second", b"sig", new_owner).result.unwrap();
+        // The address stays derived from the creator.
+        assert_eq!(fixture.record().creator, fixture.accounts[OWNER].key);
+        assert_eq!(fixture.view("second", PASSWORD).return_data.unwrap(), b"sig");
+    }
+
+    #[test]
+    fn flipped_bytes_fail_the_checksum() {
+        let mut fixture = Fixture::created(b"first");
+        let packed_len = fixture.record().packed_len().unwrap();
+        // The first entry, the bump and the checksum itself.
+        for offset in [1 + 32 + 4, packed_len - CHECKSUM_LEN - 1, packed_len - 1] {
+            let mut corrupted = fixture.accounts[RECORD].clone();
+            corrupted.data[offset] ^= 1;
+            assert_eq!(corrupted.load_record().unwrap_err(), TransacError::CorruptRecord.into(), "offset {offset}");
+        }
+    }
+
+    #[test]
+    fn expired_records_cannot_be_viewed() {
+        let options = CreateOptions {
+            expires_at: Some(1_000),
+            ..CreateOptions::default()
+        };
+        let mut fixture = Fixture::new();
+        fixture.create(b"first", options).result.unwrap();
+        fixture.runtime.clock.unix_timestamp = 1_000;
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+
+        fixture.runtime.clock.unix_timestamp = 1_001;
+        assert_eq!(fixture.view(LABEL, PASSWORD).result, err(TransacError::Expired));
+        // Refused before the password, so nothing is counted.
+        assert_eq!(fixture.view(LABEL, b"wrong horse 1").result, err(TransacError::Expired));
+        assert_eq!(fixture.record().failed_attempts, 0);
+    }
+
+    #[test]
+    fn time_locked_records_open_at_unlocks_at() {
+        let options = CreateOptions {
+            unlocks_at: Some(1_000),
+            ..CreateOptions::default()
+        };
+        let mut fixture = Fixture::new();
+        fixture.create(b"first", options).result.unwrap();
+        fixture.runtime.clock.unix_timestamp = 999;
+        assert_eq!(fixture.view(LABEL, PASSWORD).result, err(TransacError::TimeLocked));
+
+        fixture.runtime.clock.unix_timestamp = 1_000;
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn successful_views_are_counted() {
+        let mut fixture = Fixture::created(b"first");
+        for _ in 0..3 {
+            fixture.view(LABEL, PASSWORD).result.unwrap();
+        }
+        fixture.view(LABEL, b"wrong horse 1").result.unwrap();
+        fixture.verify(PASSWORD).result.unwrap();
+        assert_eq!(fixture.record().view_count, 3);
+    }
+
+    #[test]
+    fn create_batch_creates_every_record() {
+        let mut fixture = Fixture::new();
+        let owner = fixture.accounts[OWNER].key;
+        let labels = [LABEL, "second", "third"];
+        let mut indices = vec![OWNER, SYSTEM];
+        let mut entries = Vec::new();
+        for (i, label) in labels.into_iter().enumerate() {
+            let source = fixture.add_source(label.as_bytes());
+            let record = match label {
+                LABEL => RECORD,
+                _ => {
+                    let (address, _) = derive_record_address(&fixture.program_id, &owner, label.as_bytes());
+                    fixture.add(MockAccount::new(address, system_program::id(), 0).writable())
+                }
+            };
+            entries.push(instruction::CreateBatchEntry {
+                source_index: indices.len() as u8,
+                record_index: indices.len() as u8 + 1,
+                entropy: [i as u8; SALT_LEN],
+                label: label.to_string(),
+                password: PASSWORD.to_vec(),
+                options: CreateOptions::default(),
+            });
+            indices.extend([source, record]);
+        }
+        let outcome = fixture.run(&TransacInstruction::CreateBatch { entries }, &indices);
+        assert!(outcome.logs.iter().any(|log| log == "3 signatures created"));
+        assert_eq!(outcome.return_data.unwrap().len(), 3 * 33);
+
+        for (label, &record) in labels.iter().zip(indices[3..].iter().step_by(2)) {
+            let mut record = fixture.accounts[record].load_record().unwrap();
+            let key = record.check_password(PASSWORD, &fixture.runtime.clock).unwrap();
+            assert_eq!(*record.decrypt_signature(&key, label).unwrap(), label.as_bytes());
+        }
+    }
+
+    #[test]
+    fn zeroed_records_are_uninitialized() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.accounts[RECORD].data.fill(0);
+        assert_eq!(fixture.verify(PASSWORD).result, err(TransacError::AccountNotInitialized));
+        let view = TransacInstruction::View {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            nonce: 0,
+        };
+        assert_eq!(fixture.run(&view, &[RECORD]).result, err(TransacError::AccountNotInitialized));
+    }
+
+    #[test]
+    fn verify_secp256k1_checks_the_recovered_address() {
+        let secret_key = libsecp256k1::SecretKey::parse(&[5; 32]).unwrap();
+        let public_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+        let message_hash = solana_program::keccak::hash(b"pay 10 to alice").to_bytes();
+        let (signature, recovery_id) = libsecp256k1::sign(&libsecp256k1::Message::parse(&message_hash), &secret_key);
+        let mut stored = signature.serialize().to_vec();
+        stored.push(recovery_id.serialize() + 27);
+        let mut expected_address = [0; 20];
+        let digest = solana_program::keccak::hash(&public_key.serialize()[1..]).to_bytes();
+        expected_address.copy_from_slice(&digest[12..]);
+
+        let verify = |message_hash| TransacInstruction::VerifySecp256k1 {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            message_hash,
+            expected_address,
+        };
+        let mut fixture = Fixture::created(&stored);
+        let outcome = fixture.run(&verify(message_hash), &[RECORD, OWNER]);
+        assert_eq!(outcome.result, Ok(()));
+        assert!(outcome.logs.iter().any(|log| log == "Signature verified"));
+        let other_hash = solana_program::keccak::hash(b"pay 99 to alice").to_bytes();
+        assert_eq!(fixture.run(&verify(other_hash), &[RECORD, OWNER]).result, err(TransacError::SignatureInvalid));
+
+        // A corrupted `s` recovers some other key.
+        stored[40] ^= 1;
+        let mut fixture = Fixture::created(&stored);
+        assert_eq!(fixture.run(&verify(message_hash), &[RECORD, OWNER]).result, err(TransacError::SignatureInvalid));
+        stored[64] = 29;
+        let mut fixture = Fixture::created(&stored);
+        let outcome = fixture.run(&verify(message_hash), &[RECORD, OWNER]);
+        assert_eq!(outcome.result, err(TransacError::MalformedSignature));
+    }
+
+    #[test]
+    fn views_need_threshold_authorities() {
+        let mut fixture = Fixture::new();
+        let authorities: Vec<_> = (0..3)
+            .map(|_| fixture.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 1).signer()))
+            .collect();
+        let options = CreateOptions {
+            authorities: authorities.iter().map(|&i| fixture.accounts[i].key).collect(),
+            threshold: 2,
+            ..CreateOptions::default()
+        };
+        fixture.create(b"first", options).result.unwrap();
+        let stranger = fixture.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 1).signer());
+        let mut unsigned = fixture.accounts[authorities[1]].clone();
+        unsigned.is_signer = false;
+        let unsigned = fixture.add(unsigned);
+
+        let mut view = |signers: &[usize]| {
+            let view = TransacInstruction::View {
+                label: LABEL.to_string(),
+                password: PASSWORD.to_vec(),
+                nonce: fixture.record().view_nonce(),
+            };
+            fixture.run(&view, &[&[RECORD], signers].concat())
+        };
+        let insufficient = err(TransacError::InsufficientSigners);
+        assert_eq!(view(&[authorities[0]]).result, insufficient);
+        assert_eq!(view(&[authorities[0], stranger]).result, insufficient);
+        assert_eq!(view(&[authorities[0], unsigned]).result, insufficient);
+        assert_eq!(view(&[authorities[0], authorities[0]]).result, insufficient);
+        assert_eq!(view(&[authorities[0], authorities[2]]).return_data.unwrap(), b"first");
+        assert_eq!(view(&authorities).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn recent_passwords_cannot_be_reused() {
+        let mut fixture = Fixture::created(b"first");
+        assert_eq!(fixture.update_password(PASSWORD, PASSWORD).result, err(TransacError::PasswordReused));
+        fixture.update_password(PASSWORD, NEW_PASSWORD).result.unwrap();
+        assert_eq!(fixture.update_password(NEW_PASSWORD, PASSWORD).result, err(TransacError::PasswordReused));
+
+        // Once PASSWORD_HISTORY_LEN newer ones are remembered, it is forgotten.
+        let mut current = NEW_PASSWORD.to_vec();
+        for i in 0..PASSWORD_HISTORY_LEN {
+            let next = format!("password number {i}").into_bytes();
+            fixture.update_password(&current, &next).result.unwrap();
+            current = next;
+        }
+        assert_eq!(fixture.record().password_history.len(), PASSWORD_HISTORY_LEN);
+        fixture.update_password(&current, PASSWORD).result.unwrap();
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn metadata_is_set_and_read_back() {
+        let options = CreateOptions {
+            metadata: b"created".to_vec(),
+            ..CreateOptions::default()
+        };
+        let mut fixture = Fixture::new();
+        fixture.create(b"first", options).result.unwrap();
+        assert_eq!(fixture.record().metadata(), b"created");
+
+        let set_metadata = |metadata: &[u8]| TransacInstruction::SetMetadata {
+            metadata: metadata.to_vec(),
+        };
+        let longest = vec![b'm'; MAX_METADATA_LEN];
+        fixture.run(&set_metadata(&longest), &[RECORD, OWNER, SYSTEM]).result.unwrap();
+        assert_eq!(fixture.record().metadata(), longest);
+        let outcome = fixture.run(&set_metadata(&[b'm'; MAX_METADATA_LEN + 1]), &[RECORD, OWNER, SYSTEM]);
+        assert_eq!(outcome.result, err(TransacError::MetadataTooLong));
+        assert_eq!(fixture.record().metadata(), longest);
+        // The signatures are untouched.
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+    }
+
+    #[test]
+    fn replayed_views_are_stale() {
+        let mut fixture = Fixture::created(b"first");
+        let view = |nonce| TransacInstruction::View {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            nonce,
+        };
+        assert_eq!(fixture.run(&view(0), &[RECORD]).return_data.unwrap(), b"first");
+        assert_eq!(fixture.run(&view(0), &[RECORD]).result, err(TransacError::StaleNonce));
+        assert_eq!(fixture.run(&view(2), &[RECORD]).result, err(TransacError::StaleNonce));
+        assert_eq!(fixture.run(&view(1), &[RECORD]).return_data.unwrap(), b"first");
+
+        let expires_at = fixture.runtime.clock.unix_timestamp + 60;
+        let delegate = fixture.delegate([1; ACCESS_KEY_LEN], expires_at);
+        let delegate_view = TransacInstruction::DelegateView {
+            label: LABEL.to_string(),
+            access_key: [1; ACCESS_KEY_LEN],
+            nonce: 1,
+        };
+        assert_eq!(fixture.run(&delegate_view, &[RECORD, delegate]).result, err(TransacError::StaleNonce));
+
+        // Stale views are refused before the password is checked.
+        let stale = TransacInstruction::View {
+            label: LABEL.to_string(),
+            password: b"wrong horse 1".to_vec(),
+            nonce: 0,
+        };
+        assert_eq!(fixture.run(&stale, &[RECORD]).result, err(TransacError::StaleNonce));
+        assert_eq!(fixture.record().failed_attempts, 0);
+    }
+
+    #[test]
+    fn undersized_records_are_refused_before_parsing() {
+        let mut fixture = Fixture::created(b"first");
+        // With no entries, the record is the smallest one its seed label allows.
+        fixture.remove(LABEL).result.unwrap();
+        assert_eq!(fixture.record().packed_len().unwrap(), MIN_RECORD_LEN + LABEL.len());
+
+        let too_small = MIN_RECORD_LEN - 1;
+        fixture.accounts[RECORD].data.truncate(too_small);
+        let outcome = fixture.verify(PASSWORD);
+        assert_eq!(outcome.result, Err(ProgramError::AccountDataTooSmall));
+        let logged = format!("Record account holds {too_small} bytes, a record needs at least {MIN_RECORD_LEN}");
+        assert!(outcome.logs.contains(&logged));
+    }
+
+    #[test]
+    fn rotate_replaces_the_signature_under_the_same_password() {
+        let mut fixture = Fixture::created(b"first");
+        let before = fixture.record().signatures[0].clone();
+        let source = fixture.add_source(b"rotated signature");
+        let rotate = TransacInstruction::RotateSignature {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+        };
+        fixture.run(&rotate, &[source, RECORD, OWNER, SYSTEM]).result.unwrap();
+
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"rotated signature");
+        let after = fixture.record().signatures[0].clone();
+        assert_eq!(after.label, before.label);
+        assert_ne!(after.nonce, before.nonce);
+    }
+
+    #[test]
+    fn events_are_logged_as_borsh_data() {
+        // Each event is one field of one `sol_log_data` call, led by its type.
+        let events = |outcome: &MockOutcome| -> Vec<(u8, TransacEvent)> {
+            outcome
+                .data_logs
+                .iter()
+                .map(|fields| match &fields[..] {
+                    [field] => (field[0], TransacEvent::try_from_slice(field).unwrap()),
+                    _ => panic!("expected one field, got {}", fields.len()),
+                })
+                .collect()
+        };
+        let mut fixture = Fixture::new();
+        fixture.runtime.clock.unix_timestamp = 1_000;
+        let timestamp = 1_000;
+        let (record, owner) = (fixture.accounts[RECORD].key, fixture.accounts[OWNER].key);
+        let verified = (1, TransacEvent::PasswordVerified { record, timestamp });
+
+        let created = fixture.create(b"first", CreateOptions::default());
+        assert_eq!(events(&created), [(0, TransacEvent::RecordCreated { record, owner, timestamp })]);
+        assert_eq!(events(&fixture.view(LABEL, PASSWORD)), std::slice::from_ref(&verified));
+        assert!(events(&fixture.view(LABEL, b"wrong horse 1")).is_empty());
+
+        let changed = fixture.update_password(PASSWORD, NEW_PASSWORD);
+        let expected = [verified.clone(), (2, TransacEvent::PasswordChanged { record, timestamp })];
+        assert_eq!(events(&changed), expected);
+
+        let destination = fixture.add(MockAccount::new(Pubkey::new_unique(), system_program::id(), 0).writable());
+        let close = TransacInstruction::Close {
+            password: NEW_PASSWORD.to_vec(),
+        };
+        let closed = fixture.run(&close, &[RECORD, destination, OWNER]);
+        assert_eq!(events(&closed), [verified, (3, TransacEvent::RecordClosed { record, timestamp })]);
+    }
+
+    #[test]
+    fn an_all_zero_verifier_is_refused() {
+        let mut fixture = Fixture::created(b"first");
+        let mut record = fixture.record();
+        record.password_hash = [0; 32];
+        let forged = MockAccount::record(&fixture.program_id, &mut record, &fixture.runtime.rent).unwrap();
+        assert_eq!(forged.key, fixture.accounts[RECORD].key);
+        fixture.accounts[RECORD] = forged;
+
+        // The record loads, checksum and all, but no password opens it.
+        assert!(fixture.accounts[RECORD].load_record().is_ok());
+        assert_eq!(fixture.verify(PASSWORD).result, err(TransacError::AccountNotInitialized));
+        let verify = TransacInstruction::VerifyPrehashed { password_key: [0; 32] };
+        assert_eq!(fixture.run(&verify, &[RECORD]).result, err(TransacError::AccountNotInitialized));
+    }
+
+    #[test]
+    fn prehashed_passwords_match_plaintext_ones() {
+        let mut fixture = Fixture::created(b"first");
+        let (salt, iterations) = fixture.record().password_params();
+        let password_key = *crypto::derive_password_key(PASSWORD, &salt, iterations);
+
+        let verify = |password_key| TransacInstruction::VerifyPrehashed { password_key };
+        assert_eq!(fixture.run(&verify(password_key), &[RECORD]).return_data.unwrap(), [1]);
+        assert_eq!(fixture.run(&verify([0; 32]), &[RECORD]).return_data.unwrap(), [0]);
+        assert_eq!(fixture.record().failed_attempts, 1);
+
+        let view = TransacInstruction::ViewPrehashed {
+            label: LABEL.to_string(),
+            password_key,
+            nonce: 0,
+        };
+        assert_eq!(fixture.run(&view, &[RECORD]).return_data.unwrap(), b"first");
+        assert_eq!(fixture.view(LABEL, PASSWORD).return_data.unwrap(), b"first");
+        assert_eq!(fixture.record().view_count, 2);
+    }
+
+    #[test]
+    fn tallies_saturate_and_nonces_refuse_to_wrap() {
+        let mut fixture = Fixture::created(b"first");
+        fixture.rewrite_record(|record| record.view_count = u32::MAX);
+        fixture.view(LABEL, PASSWORD).result.unwrap();
+        assert_eq!(fixture.record().view_count, u32::MAX);
+
+        fixture.rewrite_record(|record| record.view_nonce = u64::MAX);
+        assert_eq!(fixture.view(LABEL, PASSWORD).result, err(TransacError::CounterOverflow));
+
+        fixture.rewrite_record(|record| record.next_nonce = u64::MAX);
+        assert_eq!(fixture.append("second", b"sig", OWNER).result, err(TransacError::CounterOverflow));
+    }
+
+    #[test]
+    fn the_stored_bump_is_the_canonical_one() {
+        let mut fixture = Fixture::new();
+        let created = fixture.create(b"first", CreateOptions::default()).return_data.unwrap();
+        let owner = fixture.accounts[OWNER].key;
+        let seeds: &[&[u8]] = &[RECORD_SEED, owner.as_ref(), LABEL.as_bytes()];
+        let (address, bump) = Pubkey::find_program_address(seeds, &fixture.program_id);
+        assert_eq!(created[..32], address.to_bytes());
+        assert_eq!(created[32], bump);
+        assert_eq!(fixture.record().bump(), bump);
+
+        // Any other bump fails the address check, even under a valid checksum.
+        let mut record = fixture.record();
+        record.bump = bump.wrapping_sub(1);
+        let mut bytes = borsh::to_vec(&record).unwrap();
+        bytes.extend_from_slice(&record_checksum(&bytes));
+        fixture.accounts[RECORD].data[..bytes.len()].copy_from_slice(&bytes);
+        assert_eq!(fixture.verify(PASSWORD).result, Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn unknown_opcodes_are_reported() {
+        let mut fixture = Fixture::created(b"first");
+        let unknown = TransacInstruction::LAST_OPCODE + 1;
+        for data in [vec![unknown], vec![unknown, 1, 2, 3], vec![u8::MAX]] {
+            let outcome = fixture.run_raw(&data, &[RECORD]);
+            assert_eq!(outcome.result, Err(ProgramError::InvalidInstructionData));
+            assert_eq!(outcome.logs, [format!("Unknown opcode {}", data[0])]);
+        }
+
+        // A known opcode with bad fields is reported as malformed instead.
+        let verify = instruction::Opcode::Verify as u8;
+        let outcome = fixture.run_raw(&[verify, 1], &[RECORD]);
+        assert_eq!(outcome.result, Err(ProgramError::InvalidInstructionData));
+        assert_eq!(outcome.logs, [format!("Malformed data for opcode {verify}")]);
+    }
+}
@@ -0,0 +1,103 @@
+//! Checks of stored signatures against the runtime's signature precompiles.
+
+use solana_program::{
+    ed25519_program,
+    instruction::Instruction,
+    keccak,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    secp256k1_recover::{secp256k1_recover, SECP256K1_SIGNATURE_LENGTH},
+};
+
+use crate::error::TransacError;
+
+/// Length of an ed25519 signature.
+pub const ED25519_SIGNATURE_LEN: usize = 64;
+
+/// Where the first offsets block of an ed25519 precompile instruction
+/// starts, after the signature count and a padding byte.
+pub const ED25519_OFFSETS_START: usize = 2;
+
+/// Size of one offsets block: seven `u16` LE fields.
+pub const ED25519_OFFSETS_LEN: usize = 14;
+
+/// Length of a recoverable secp256k1 signature: `r || s` followed by the
+/// recovery id.
+pub const SECP256K1_RECOVERABLE_SIGNATURE_LEN: usize = SECP256K1_SIGNATURE_LENGTH + 1;
+
+/// Length of an Ethereum address.
+pub const ETH_ADDRESS_LEN: usize = 20;
+
+/// Instruction index meaning "the precompile instruction itself".
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Requires `ix` to be an ed25519 precompile instruction that verified exactly
+/// `signature` by `signer` over `message`.
+///
+/// The precompile fails the whole transaction on a bad signature, so by the
+/// time this program runs any precompile instruction present has passed; all
+/// that is left is to check it covered the data we care about. Only data held
+/// in the precompile instruction itself is accepted, so its offsets can't
+/// point at bytes some other instruction controls.
+pub fn check_ed25519_instruction(
+    ix: &Instruction,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), ProgramError> {
+    let mismatch = || -> ProgramError { TransacError::SignatureInvalid.into() };
+    if ix.program_id != ed25519_program::id() || signature.len() != ED25519_SIGNATURE_LEN {
+        return Err(mismatch());
+    }
+
+    let data = &ix.data;
+    if data.first() != Some(&1) {
+        return Err(mismatch());
+    }
+    let offsets = data
+        .get(ED25519_OFFSETS_START..ED25519_OFFSETS_START + ED25519_OFFSETS_LEN)
+        .ok_or_else(mismatch)?;
+    let field = |i: usize| u16::from_le_bytes([offsets[2 * i], offsets[2 * i + 1]]);
+    let [signature_offset, signature_ix, pubkey_offset, pubkey_ix, message_offset, message_len, message_ix] =
+        [0, 1, 2, 3, 4, 5, 6].map(field);
+    if [signature_ix, pubkey_ix, message_ix] != [CURRENT_INSTRUCTION; 3] {
+        return Err(mismatch());
+    }
+
+    let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+    let covered = slice(signature_offset, ED25519_SIGNATURE_LEN) == Some(signature)
+        && slice(pubkey_offset, 32) == Some(signer.as_ref())
+        && message_len as usize == message.len()
+        && slice(message_offset, message.len()) == Some(message);
+    if !covered {
+        return Err(mismatch());
+    }
+    Ok(())
+}
+
+/// Recovers the Ethereum address that produced `signature` over
+/// `message_hash`.
+///
+/// The recovery id may be given raw (0 or 1) or Ethereum-style (27 or 28).
+/// Anything that isn't a well-formed recoverable signature is reported as
+/// [`TransacError::MalformedSignature`].
+pub fn recover_secp256k1_address(
+    message_hash: &[u8; 32],
+    signature: &[u8],
+) -> Result<[u8; ETH_ADDRESS_LEN], ProgramError> {
+    if signature.len() != SECP256K1_RECOVERABLE_SIGNATURE_LEN {
+        return Err(TransacError::MalformedSignature.into());
+    }
+    let (rs, v) = signature.split_at(SECP256K1_SIGNATURE_LENGTH);
+    let recovery_id = match v[0] {
+        0 | 1 => v[0],
+        27 | 28 => v[0] - 27,
+        _ => return Err(TransacError::MalformedSignature.into()),
+    };
+
+    let pubkey = secp256k1_recover(message_hash, recovery_id, rs).map_err(|_| TransacError::MalformedSignature)?;
+    let digest = keccak::hash(&pubkey.to_bytes()).to_bytes();
+    let mut address = [0u8; ETH_ADDRESS_LEN];
+    address.copy_from_slice(&digest[32 - ETH_ADDRESS_LEN..]);
+    Ok(address)
+}
@@ -0,0 +1,498 @@
+//! In-memory stand-ins for the runtime, for testing the processor without a
+//! validator or `solana-program-test`.
+//!
+//! [`MockRuntime::process`] lays the accounts out exactly as the loader
+//! does, runs [`process_instruction`] over them, and copies the results
+//! back. Sysvars, return data and logs go through syscall stubs it installs
+//! for the duration of the call. Cross-program invocations of the system
+//! program's `CreateAccount`, `Transfer`, `Allocate` and `Assign` are
+//! carried out, as are invocations of programs registered in
+//! [`MockRuntime::programs`]; any other invocation is logged and treated as
+//! a success.
+//!
+//! ```
+//! use solana_contract::{instruction::TransacInstruction, test_support::*};
+//! use solana_program::{pubkey::Pubkey, system_program};
+//!
+//! let program_id = Pubkey::new_unique();
+//! let owner = Pubkey::new_unique();
+//! let (record, _) = solana_contract::derive_record_address(&program_id, &owner, b"main");
+//! let mut accounts = [
+//!     MockAccount::new(record, system_program::id(), 0).writable(),
+//!     MockAccount::new(owner, system_program::id(), 1_000_000_000).writable().signer(),
+//!     MockAccount::new(system_program::id(), Pubkey::default(), 1),
+//! ];
+//! let runtime = MockRuntime::default();
+//! let create = TransacInstruction::CreateInline {
+//!     entropy: [7; 16],
+//!     label: "main".to_string(),
+//!     signature: b"signed".to_vec(),
+//!     password: b"correct horse 1".to_vec(),
+//!     options: Default::default(),
+//! };
+//! runtime.process(&program_id, &mut accounts, &borsh::to_vec(&create).unwrap()).result.unwrap();
+//!
+//! let verify = TransacInstruction::Verify { password: b"correct horse 1".to_vec() };
+//! let outcome = runtime.process(&program_id, &mut accounts[..1], &borsh::to_vec(&verify).unwrap());
+//! assert_eq!(outcome.return_data, Some(vec![1]));
+//! ```
+
+use std::{
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{
+        deserialize, ProcessInstruction, ProgramResult, BPF_ALIGN_OF_U128, MAX_PERMITTED_DATA_INCREASE,
+        NON_DUP_MARKER,
+    },
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::{SystemError, SystemInstruction},
+    system_program,
+};
+
+use crate::{process_instruction, SignatureRecord, RECORD_SEED};
+
+/// The syscall stubs are process-wide, so only one instruction runs at a
+/// time even when tests run in parallel.
+static RUNTIME_LOCK: Mutex<()> = Mutex::new(());
+
+/// An account as the test owns it, outside of any instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub executable: bool,
+}
+
+impl MockAccount {
+    /// A read-only, unsigned account with no data.
+    pub fn new(key: Pubkey, owner: Pubkey, lamports: u64) -> Self {
+        MockAccount {
+            key,
+            owner,
+            lamports,
+            data: Vec::new(),
+            is_signer: false,
+            is_writable: false,
+            executable: false,
+        }
+    }
+
+    /// A rent-exempt account owned by `program_id` holding `record`, at the
+    /// canonical address for the record's seeds.
+    pub fn record(program_id: &Pubkey, record: &mut SignatureRecord, rent: &Rent) -> Result<Self, ProgramError> {
+        let seeds: &[&[u8]] = &[RECORD_SEED, record.creator.as_ref(), record.seed_label.as_bytes()];
+        let (key, bump) = Pubkey::find_program_address(seeds, program_id);
+        record.bump = bump;
+
+        let space = record.allocation_len()?;
+        let mut account = MockAccount::new(key, *program_id, rent.minimum_balance(space)).writable();
+        account.data = vec![0; space];
+        record.save(&account.info(&mut 0))?;
+        Ok(account)
+    }
+
+    pub fn signer(mut self) -> Self {
+        self.is_signer = true;
+        self
+    }
+
+    pub fn writable(mut self) -> Self {
+        self.is_writable = true;
+        self
+    }
+
+    /// Reads back the record the account holds.
+    pub fn load_record(&mut self) -> Result<SignatureRecord, ProgramError> {
+        SignatureRecord::load(&self.info(&mut 0))
+    }
+
+    /// Borrows the account as an `AccountInfo` that can't be resized. The
+    /// lamports are taken from `lamports` rather than the account.
+    fn info<'a>(&'a mut self, lamports: &'a mut u64) -> AccountInfo<'a> {
+        *lamports = self.lamports;
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            lamports,
+            &mut self.data,
+            &self.owner,
+            self.executable,
+            0,
+        )
+    }
+}
+
+/// What an instruction run through [`MockRuntime::process`] left behind.
+#[derive(Debug)]
+pub struct MockOutcome {
+    pub result: ProgramResult,
+    /// Every `msg!` line, in order.
+    pub logs: Vec<String>,
+    /// The fields of every `sol_log_data` call, in order. Each
+    /// [`TransacEvent`](crate::event::TransacEvent) is one call of one field.
+    pub data_logs: Vec<Vec<Vec<u8>>>,
+    /// Return data, if the instruction set any.
+    pub return_data: Option<Vec<u8>>,
+}
+
+/// Sysvars and programs seen by instructions run through
+/// [`MockRuntime::process`].
+#[derive(Clone, Debug, Default)]
+pub struct MockRuntime {
+    pub clock: Clock,
+    pub rent: Rent,
+    /// Programs that cross-program invocations can reach, run natively.
+    pub programs: Vec<(Pubkey, ProcessInstruction)>,
+}
+
+impl MockRuntime {
+    /// Runs `instruction_data` against `accounts` and writes the resulting
+    /// lamports, data and owners back into them, whether or not the
+    /// instruction succeeded. Unlike the runtime, a failed instruction's
+    /// writes are kept, which lets tests inspect them.
+    pub fn process(&self, program_id: &Pubkey, accounts: &mut [MockAccount], instruction_data: &[u8]) -> MockOutcome {
+        let _guard = RUNTIME_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let state = Arc::new(Mutex::new(StubState::default()));
+        let previous = set_syscall_stubs(Box::new(Stubs {
+            clock: self.clock.clone(),
+            rent: self.rent,
+            program_id: *program_id,
+            programs: self.programs.clone(),
+            state: Arc::clone(&state),
+        }));
+
+        let (mut input, key_offsets) = serialize(program_id, accounts, instruction_data);
+        let base = input.as_mut_ptr() as *mut u8;
+        let result = {
+            // SAFETY: `input` is laid out as the loader would lay it out, with
+            // room for every account to grow, and outlives the account infos.
+            let (program_id, account_infos, instruction_data) = unsafe { deserialize(base) };
+            process_instruction(program_id, &account_infos, instruction_data)
+        };
+        for (i, offset) in key_offsets.into_iter().enumerate() {
+            match offset {
+                // SAFETY: `offset` is where `serialize` put this account's key.
+                Some(offset) => unsafe { read_back(base.add(offset), &mut accounts[i]) },
+                None => {
+                    let first = accounts.iter().position(|account| account.key == accounts[i].key).unwrap();
+                    accounts[i] = MockAccount {
+                        is_signer: accounts[i].is_signer,
+                        is_writable: accounts[i].is_writable,
+                        ..accounts[first].clone()
+                    };
+                }
+            }
+        }
+
+        set_syscall_stubs(previous);
+        let state = std::mem::take(&mut *state.lock().unwrap());
+        MockOutcome {
+            result,
+            logs: state.logs,
+            data_logs: state.data_logs,
+            return_data: state.return_data,
+        }
+    }
+}
+
+#[derive(Default)]
+struct StubState {
+    logs: Vec<String>,
+    data_logs: Vec<Vec<Vec<u8>>>,
+    return_data: Option<Vec<u8>>,
+}
+
+struct Stubs {
+    clock: Clock,
+    rent: Rent,
+    program_id: Pubkey,
+    programs: Vec<(Pubkey, ProcessInstruction)>,
+    state: Arc<Mutex<StubState>>,
+}
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, message: &str) {
+        self.state.lock().unwrap().logs.push(message.to_string());
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields = fields.iter().map(|field| field.to_vec()).collect();
+        self.state.lock().unwrap().data_logs.push(fields);
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to a `Clock`.
+        unsafe { *(var_addr as *mut Clock) = self.clock.clone() };
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to a `Rent`.
+        unsafe { *(var_addr as *mut Rent) = self.rent };
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.state.lock().unwrap().return_data = (!data.is_empty()).then(|| data.to_vec());
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        let data = self.state.lock().unwrap().return_data.clone()?;
+        Some((self.program_id, data))
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if instruction.program_id != system_program::id() {
+            return self.invoke_program(instruction, account_infos, signers_seeds);
+        }
+        let find = |index: usize| -> Result<&AccountInfo, ProgramError> {
+            let key = instruction.accounts.get(index).ok_or(ProgramError::NotEnoughAccountKeys)?.pubkey;
+            account_infos
+                .iter()
+                .find(|info| *info.key == key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+            let remaining = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+            **from.try_borrow_mut_lamports()? = remaining;
+            **to.try_borrow_mut_lamports()? += lamports;
+            Ok(())
+        };
+
+        match limited_deserialize(&instruction.data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+            SystemInstruction::CreateAccount { lamports, space, owner } => {
+                let (from, to) = (find(0)?, find(1)?);
+                if to.lamports() != 0 || !to.data_is_empty() {
+                    return Err(ProgramError::Custom(SystemError::AccountAlreadyInUse as u32));
+                }
+                transfer(from, to, lamports)?;
+                to.realloc(space as usize, true)?;
+                to.assign(&owner);
+                Ok(())
+            }
+            SystemInstruction::Transfer { lamports } => transfer(find(0)?, find(1)?, lamports),
+            SystemInstruction::Allocate { space } => {
+                let account = find(0)?;
+                if *account.owner != system_program::id() || !account.data_is_empty() {
+                    return Err(ProgramError::Custom(SystemError::AccountAlreadyInUse as u32));
+                }
+                account.realloc(space as usize, true)
+            }
+            SystemInstruction::Assign { owner } => {
+                find(0)?.assign(&owner);
+                Ok(())
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+impl Stubs {
+    /// Runs a registered program the way the runtime would: over the
+    /// caller's accounts, as flagged in `instruction`. A flag the caller
+    /// doesn't hold itself, or sign for with `signers_seeds`, is refused; the
+    /// runtime's privilege escalation error isn't a `ProgramError`, so
+    /// `MissingRequiredSignature` and `InvalidArgument` stand in for it.
+    fn invoke_program(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let Some((_, processor)) = self.programs.iter().find(|(id, _)| *id == instruction.program_id) else {
+            self.sol_log(&format!("Mock: invoked {}", instruction.program_id));
+            return Ok(());
+        };
+        let signed: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &self.program_id))
+            .collect::<Result<_, _>>()?;
+        let mut callee_infos = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let may_sign = info.is_signer || signed.contains(info.key);
+            if meta.is_signer && !may_sign {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let mut info = info.clone();
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            callee_infos.push(info);
+        }
+        processor(&instruction.program_id, &callee_infos, &instruction.data)
+    }
+}
+
+/// Lays out the entrypoint input the way the loader does. Returns it along
+/// with where each account's key sits, or `None` for a repeated account.
+fn serialize(program_id: &Pubkey, accounts: &[MockAccount], instruction_data: &[u8]) -> (Vec<u64>, Vec<Option<usize>>) {
+    let mut bytes = Vec::new();
+    let mut key_offsets = Vec::with_capacity(accounts.len());
+    bytes.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+    for (i, account) in accounts.iter().enumerate() {
+        if let Some(first) = accounts[..i].iter().position(|earlier| earlier.key == account.key) {
+            bytes.push(first as u8);
+            bytes.extend_from_slice(&[0; 7]);
+            key_offsets.push(None);
+            continue;
+        }
+        let flags = [account.is_signer, account.is_writable, account.executable];
+        bytes.push(NON_DUP_MARKER);
+        bytes.extend(flags.map(u8::from));
+        // Original data length, filled in by `deserialize`.
+        bytes.extend_from_slice(&[0; size_of::<u32>()]);
+        key_offsets.push(Some(bytes.len()));
+        bytes.extend_from_slice(account.key.as_ref());
+        bytes.extend_from_slice(account.owner.as_ref());
+        bytes.extend_from_slice(&account.lamports.to_le_bytes());
+        bytes.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&account.data);
+        bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        bytes.resize(bytes.len().next_multiple_of(BPF_ALIGN_OF_U128), 0);
+        // Rent epoch.
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+    }
+    bytes.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(instruction_data);
+    bytes.extend_from_slice(program_id.as_ref());
+
+    // Backed by `u64`s so the input is aligned as the runtime aligns it.
+    let mut input = vec![0u64; bytes.len().div_ceil(size_of::<u64>())];
+    // SAFETY: `input` spans at least `bytes.len()` bytes.
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), input.as_mut_ptr() as *mut u8, bytes.len()) };
+    (input, key_offsets)
+}
+
+/// Copies the owner, lamports and data serialized at `key` back into
+/// `account`.
+///
+/// # Safety
+///
+/// `key` must point at an account's key within input laid out by
+/// [`serialize`].
+unsafe fn read_back(key: *const u8, account: &mut MockAccount) {
+    let owner = key.add(size_of::<Pubkey>());
+    let lamports = owner.add(size_of::<Pubkey>());
+    let data_len = lamports.add(size_of::<u64>());
+    let data = data_len.add(size_of::<u64>());
+
+    account.owner = Pubkey::new_from_array(*(owner as *const [u8; 32]));
+    account.lamports = (lamports as *const u64).read_unaligned();
+    let len = (data_len as *const u64).read_unaligned() as usize;
+    account.data = std::slice::from_raw_parts(data, len).to_vec();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{derive_record_address, instruction::TransacInstruction};
+
+    const PASSWORD: &[u8] = b"correct horse 1";
+
+    fn create(runtime: &MockRuntime, program_id: &Pubkey, accounts: &mut [MockAccount]) -> MockOutcome {
+        let create = TransacInstruction::CreateInline {
+            entropy: [7; 16],
+            label: "main".to_string(),
+            signature: b"signed".to_vec(),
+            password: PASSWORD.to_vec(),
+            options: Default::default(),
+        };
+        runtime.process(program_id, accounts, &borsh::to_vec(&create).unwrap())
+    }
+
+    fn accounts(program_id: &Pubkey) -> [MockAccount; 3] {
+        let owner = Pubkey::new_unique();
+        let (record, _) = derive_record_address(program_id, &owner, b"main");
+        [
+            MockAccount::new(record, system_program::id(), 0).writable(),
+            MockAccount::new(owner, system_program::id(), 1_000_000_000).writable().signer(),
+            MockAccount::new(system_program::id(), Pubkey::default(), 1),
+        ]
+    }
+
+    #[test]
+    fn create_then_verify() {
+        let program_id = Pubkey::new_unique();
+        let runtime = MockRuntime::default();
+        let mut accounts = accounts(&program_id);
+        let outcome = create(&runtime, &program_id, &mut accounts);
+        assert_eq!(outcome.result, Ok(()));
+        assert!(outcome.logs.iter().any(|log| log == "Signature created successfully"));
+
+        // The system program's work is carried back into the accounts.
+        let space = accounts[0].data.len();
+        assert_eq!(accounts[0].owner, program_id);
+        assert_eq!(accounts[0].lamports, runtime.rent.minimum_balance(space));
+        assert_eq!(accounts[1].lamports, 1_000_000_000 - accounts[0].lamports);
+
+        for (password, verified) in [(PASSWORD, 1), (b"wrong horse 1".as_slice(), 0)] {
+            let verify = TransacInstruction::Verify { password: password.to_vec() };
+            let outcome = runtime.process(&program_id, &mut accounts[..1], &borsh::to_vec(&verify).unwrap());
+            assert_eq!(outcome.result, Ok(()));
+            assert_eq!(outcome.return_data, Some(vec![verified]));
+        }
+        assert_eq!(accounts[0].load_record().unwrap().failed_attempts, 1);
+    }
+
+    #[test]
+    fn system_program_refuses_an_account_in_use() {
+        let program_id = Pubkey::new_unique();
+        let runtime = MockRuntime::default();
+        let mut accounts = accounts(&program_id);
+        assert_eq!(create(&runtime, &program_id, &mut accounts).result, Ok(()));
+
+        // Emptied of data, the record still has its owner and lamports.
+        accounts[0].data.clear();
+        let outcome = create(&runtime, &program_id, &mut accounts);
+        assert_eq!(outcome.result, Err(ProgramError::Custom(SystemError::AccountAlreadyInUse as u32)));
+    }
+
+    #[test]
+    fn records_are_placed_at_their_address() {
+        let program_id = Pubkey::new_unique();
+        let runtime = MockRuntime::default();
+        let mut accounts = accounts(&program_id);
+        assert_eq!(create(&runtime, &program_id, &mut accounts).result, Ok(()));
+
+        let mut record = accounts[0].load_record().unwrap();
+        let placed = MockAccount::record(&program_id, &mut record, &runtime.rent).unwrap();
+        assert_eq!(placed.key, accounts[0].key);
+        assert_eq!(placed.data, accounts[0].data);
+    }
+
+    #[test]
+    fn runtime_sysvars_reach_the_program() {
+        let program_id = Pubkey::new_unique();
+        let mut runtime = MockRuntime::default();
+        runtime.clock.unix_timestamp = 1_700_000_000;
+        let mut accounts = accounts(&program_id);
+        assert_eq!(create(&runtime, &program_id, &mut accounts).result, Ok(()));
+        assert_eq!(accounts[0].load_record().unwrap().created_at, 1_700_000_000);
+    }
+}
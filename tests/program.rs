@@ -0,0 +1,660 @@
+//! End-to-end tests against a local bank, through `solana-program-test`.
+//!
+//! Each test drives the program with real transactions: accounts are created
+//! through the system program, sysvars come from the bank, and errors come
+//! back as transaction errors.
+
+use borsh::BorshDeserialize;
+use solana_contract::{
+    client::ed25519_precompile_ix,
+    derive_record_address,
+    error::TransacError,
+    instruction::{CreateBatchEntry, CreateOptions, DelegateGrant, TransacInstruction},
+    process_instruction, SignatureRecord, MAX_METADATA_LEN, MAX_SIGNATURE_LEN, SALT_LEN,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+const PASSWORD: &[u8] = b"correct horse 1";
+const NEW_PASSWORD: &[u8] = b"battery staple 2";
+const WRONG_PASSWORD: &[u8] = b"wrong horse 1";
+const SIGNATURE: &[u8] = b"signed by the owner";
+const LABEL: &str = "main";
+
+/// A bank with the program deployed, plus a funded payer that owns every
+/// record the tests create.
+struct Harness {
+    context: ProgramTestContext,
+    program_id: Pubkey,
+    /// Account whose data is [`SIGNATURE`], for the instructions that read
+    /// signatures from a source account.
+    source: Pubkey,
+    /// Folded into every transaction's compute budget, so that identical
+    /// instructions sent twice still make distinct transactions.
+    sent: u32,
+}
+
+/// What a transaction left behind.
+struct Sent {
+    result: Result<(), TransactionError>,
+    return_data: Option<Vec<u8>>,
+    logs: Vec<String>,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("solana_contract", program_id, processor!(process_instruction));
+        program_test.add_account(
+            source,
+            Account {
+                lamports: 1_000_000_000,
+                data: SIGNATURE.to_vec(),
+                owner: system_program::id(),
+                ..Account::default()
+            },
+        );
+        Harness {
+            context: program_test.start_with_context().await,
+            program_id,
+            source,
+            sent: 0,
+        }
+    }
+
+    /// Sends `instructions`, signed by the payer and `signers`. A compute
+    /// budget instruction goes first, so the program's instructions start at
+    /// index 1.
+    async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Sent {
+        self.sent += 1;
+        let mut instructions = instructions.to_vec();
+        instructions.insert(0, ComputeBudgetInstruction::set_compute_unit_limit(1_000_000 + self.sent));
+        let payer = &self.context.payer;
+        let mut all_signers = vec![payer];
+        all_signers.extend_from_slice(signers);
+        let blockhash = self.context.last_blockhash;
+        let transaction =
+            Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &all_signers, blockhash);
+        let outcome = self.context.banks_client.process_transaction_with_metadata(transaction).await.unwrap();
+        let metadata = outcome.metadata.unwrap();
+        Sent {
+            result: outcome.result,
+            return_data: metadata.return_data.map(|return_data| return_data.data).filter(|data| !data.is_empty()),
+            logs: metadata.log_messages,
+        }
+    }
+
+    fn instruction(&self, instruction: &TransacInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction::new_with_borsh(self.program_id, instruction, accounts)
+    }
+
+    fn address(&self, label: &str) -> Pubkey {
+        derive_record_address(&self.program_id, &self.context.payer.pubkey(), label.as_bytes()).0
+    }
+
+    fn owner(&self) -> AccountMeta {
+        AccountMeta::new(self.context.payer.pubkey(), true)
+    }
+
+    /// Creates the payer's record named `label`, holding [`SIGNATURE`].
+    async fn create(&mut self, label: &str, options: CreateOptions) -> Sent {
+        let create = self.instruction(
+            &TransacInstruction::Create {
+                entropy: [7; SALT_LEN],
+                label: label.to_string(),
+                password: PASSWORD.to_vec(),
+                options,
+            },
+            vec![
+                AccountMeta::new_readonly(self.source, false),
+                AccountMeta::new(self.address(label), false),
+                self.owner(),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        self.send(&[create], &[]).await
+    }
+
+    /// A harness whose payer already owns a record under [`LABEL`].
+    async fn created() -> Self {
+        let mut harness = Harness::new().await;
+        harness.create(LABEL, CreateOptions::default()).await.result.unwrap();
+        harness
+    }
+
+    async fn record(&mut self, address: Pubkey) -> SignatureRecord {
+        let account = self.context.banks_client.get_account(address).await.unwrap().unwrap();
+        SignatureRecord::deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Runs an instruction on the payer's [`LABEL`] record that takes the
+    /// record, then the owner.
+    async fn owner_op(&mut self, instruction: &TransacInstruction) -> Sent {
+        let accounts = vec![AccountMeta::new(self.address(LABEL), false), self.owner()];
+        let instruction = self.instruction(instruction, accounts);
+        self.send(&[instruction], &[]).await
+    }
+
+    async fn verify(&mut self, password: &[u8]) -> Sent {
+        let verify = self.instruction(
+            &TransacInstruction::Verify {
+                password: password.to_vec(),
+            },
+            vec![AccountMeta::new(self.address(LABEL), false)],
+        );
+        self.send(&[verify], &[]).await
+    }
+
+    async fn view(&mut self, label: &str, password: &[u8]) -> Sent {
+        let address = self.address(LABEL);
+        let nonce = self.record(address).await.view_nonce();
+        let view = self.instruction(
+            &TransacInstruction::View {
+                label: label.to_string(),
+                password: password.to_vec(),
+                nonce,
+            },
+            vec![AccountMeta::new(address, false)],
+        );
+        self.send(&[view], &[]).await
+    }
+
+    async fn now(&mut self) -> i64 {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+    }
+
+    /// Moves the bank's clock to `unix_timestamp`.
+    async fn warp_to_time(&mut self, unix_timestamp: i64) {
+        let clock = self.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        self.context.set_sysvar(&Clock { unix_timestamp, ..clock });
+    }
+}
+
+fn program_error(error: InstructionError) -> Result<(), TransactionError> {
+    Err(TransactionError::InstructionError(1, error))
+}
+
+fn transac_error(error: TransacError) -> Result<(), TransactionError> {
+    program_error(InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn create_then_verify_and_view() {
+    let mut harness = Harness::new().await;
+    let created = harness.create(LABEL, CreateOptions::default()).await;
+    assert_eq!(created.result, Ok(()));
+    let (address, bump) = derive_record_address(&harness.program_id, &harness.context.payer.pubkey(), LABEL.as_bytes());
+    let mut expected = address.to_bytes().to_vec();
+    expected.push(bump);
+    assert_eq!(created.return_data.unwrap(), expected);
+
+    let verified = harness.verify(PASSWORD).await;
+    assert_eq!(verified.result, Ok(()));
+    assert_eq!(verified.return_data.unwrap(), [1]);
+
+    let viewed = harness.view(LABEL, PASSWORD).await;
+    assert_eq!(viewed.result, Ok(()));
+    assert_eq!(viewed.return_data.unwrap(), SIGNATURE);
+    assert_eq!(harness.record(address).await.view_nonce(), 1);
+}
+
+#[tokio::test]
+async fn wrong_password_is_counted_and_reveals_nothing() {
+    let mut harness = Harness::created().await;
+    let verified = harness.verify(WRONG_PASSWORD).await;
+    assert_eq!(verified.result, Ok(()));
+    assert_eq!(verified.return_data.unwrap(), [0]);
+
+    let viewed = harness.view(LABEL, WRONG_PASSWORD).await;
+    assert_eq!(viewed.result, Ok(()));
+    assert_eq!(viewed.return_data, None);
+    assert!(viewed.logs.iter().any(|log| log.contains("Wrong password (2 failed attempts)")));
+}
+
+#[tokio::test]
+async fn create_refuses_an_address_for_another_label() {
+    let mut harness = Harness::new().await;
+    let create = harness.instruction(
+        &TransacInstruction::Create {
+            entropy: [7; SALT_LEN],
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            options: CreateOptions::default(),
+        },
+        vec![
+            AccountMeta::new_readonly(harness.source, false),
+            AccountMeta::new(harness.address("other"), false),
+            harness.owner(),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert_eq!(harness.send(&[create], &[]).await.result, program_error(InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn view_refuses_a_stale_nonce() {
+    let mut harness = Harness::created().await;
+    harness.view(LABEL, PASSWORD).await.result.unwrap();
+    let view = harness.instruction(
+        &TransacInstruction::View {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            nonce: 0,
+        },
+        vec![AccountMeta::new(harness.address(LABEL), false)],
+    );
+    assert_eq!(harness.send(&[view], &[]).await.result, transac_error(TransacError::StaleNonce));
+}
+
+#[tokio::test]
+async fn view_refuses_an_expired_record() {
+    let mut harness = Harness::new().await;
+    let expires_at = harness.now().await + 3_600;
+    let options = CreateOptions {
+        expires_at: Some(expires_at),
+        ..CreateOptions::default()
+    };
+    harness.create(LABEL, options).await.result.unwrap();
+    assert_eq!(harness.view(LABEL, PASSWORD).await.return_data.unwrap(), SIGNATURE);
+
+    harness.warp_to_time(expires_at).await;
+    assert_eq!(harness.view(LABEL, PASSWORD).await.return_data.unwrap(), SIGNATURE);
+
+    harness.warp_to_time(expires_at + 1).await;
+    let viewed = harness.view(LABEL, PASSWORD).await;
+    assert_eq!(viewed.result, transac_error(TransacError::Expired));
+    assert_eq!(viewed.return_data, None);
+}
+
+#[tokio::test]
+async fn update_password_refuses_a_wrong_current_password() {
+    let mut harness = Harness::created().await;
+    let update = |current_password: &[u8]| TransacInstruction::UpdatePassword {
+        current_password: current_password.to_vec(),
+        new_password: NEW_PASSWORD.to_vec(),
+    };
+    let sent = harness.owner_op(&update(WRONG_PASSWORD)).await;
+    assert_eq!(sent.result, transac_error(TransacError::WrongPassword));
+
+    harness.owner_op(&update(PASSWORD)).await.result.unwrap();
+    assert_eq!(harness.verify(PASSWORD).await.return_data.unwrap(), [0]);
+    assert_eq!(harness.view(LABEL, NEW_PASSWORD).await.return_data.unwrap(), SIGNATURE);
+}
+
+#[tokio::test]
+async fn close_refuses_a_stranger() {
+    let mut harness = Harness::created().await;
+    let stranger = Keypair::new();
+    let destination = Pubkey::new_unique();
+    let close = |owner: Pubkey| {
+        harness.instruction(
+            &TransacInstruction::Close {
+                password: PASSWORD.to_vec(),
+            },
+            vec![
+                AccountMeta::new(harness.address(LABEL), false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner, true),
+            ],
+        )
+    };
+    let by_stranger = close(stranger.pubkey());
+    let by_owner = close(harness.context.payer.pubkey());
+    let sent = harness.send(&[by_stranger], &[&stranger]).await;
+    assert_eq!(sent.result, transac_error(TransacError::OwnerMismatch));
+
+    let address = harness.address(LABEL);
+    let lamports = harness.context.banks_client.get_balance(address).await.unwrap();
+    harness.send(&[by_owner], &[]).await.result.unwrap();
+    assert_eq!(harness.context.banks_client.get_account(address).await.unwrap(), None);
+    assert_eq!(harness.context.banks_client.get_balance(destination).await.unwrap(), lamports);
+}
+
+#[tokio::test]
+async fn append_refuses_a_duplicate_label() {
+    let mut harness = Harness::created().await;
+    let append = |label: &str| {
+        harness.instruction(
+            &TransacInstruction::AppendSignature {
+                label: label.to_string(),
+                password: PASSWORD.to_vec(),
+            },
+            vec![
+                AccountMeta::new_readonly(harness.source, false),
+                AccountMeta::new(harness.address(LABEL), false),
+                harness.owner(),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    };
+    let (duplicate, second) = (append(LABEL), append("second"));
+    assert_eq!(harness.send(&[duplicate], &[]).await.result, transac_error(TransacError::DuplicateLabel));
+
+    harness.send(&[second], &[]).await.result.unwrap();
+    assert_eq!(harness.view("second", PASSWORD).await.return_data.unwrap(), SIGNATURE);
+}
+
+#[tokio::test]
+async fn transfer_ownership_refuses_a_wrong_password() {
+    let mut harness = Harness::created().await;
+    let transfer = TransacInstruction::TransferOwnership {
+        password: WRONG_PASSWORD.to_vec(),
+        new_owner: Pubkey::new_unique(),
+    };
+    assert_eq!(harness.owner_op(&transfer).await.result, transac_error(TransacError::WrongPassword));
+}
+
+#[tokio::test]
+async fn recover_needs_a_configured_secret() {
+    let mut harness = Harness::created().await;
+    let recover = TransacInstruction::Recover {
+        recovery_secret: [9; 32],
+        new_password: NEW_PASSWORD.to_vec(),
+    };
+    assert_eq!(harness.owner_op(&recover).await.result, transac_error(TransacError::RecoveryNotConfigured));
+}
+
+#[tokio::test]
+async fn recover_resets_the_password() {
+    let mut harness = Harness::new().await;
+    let options = CreateOptions {
+        recovery_secret: Some([9; 32]),
+        ..CreateOptions::default()
+    };
+    harness.create(LABEL, options).await.result.unwrap();
+    let recover = |recovery_secret| TransacInstruction::Recover {
+        recovery_secret,
+        new_password: NEW_PASSWORD.to_vec(),
+    };
+    let sent = harness.owner_op(&recover([8; 32])).await;
+    assert_eq!(sent.result, transac_error(TransacError::WrongRecoveryKey));
+
+    harness.owner_op(&recover([9; 32])).await.result.unwrap();
+    assert_eq!(harness.view(LABEL, NEW_PASSWORD).await.return_data.unwrap(), SIGNATURE);
+}
+
+#[tokio::test]
+async fn create_batch_is_all_or_nothing() {
+    let mut harness = Harness::new().await;
+    let entry = |label: &str, password: &[u8]| CreateBatchEntry {
+        source_index: 2,
+        record_index: 3,
+        entropy: [7; SALT_LEN],
+        label: label.to_string(),
+        password: password.to_vec(),
+        options: CreateOptions::default(),
+    };
+    let accounts = vec![
+        harness.owner(),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(harness.source, false),
+        AccountMeta::new(harness.address(LABEL), false),
+        AccountMeta::new(harness.address("second"), false),
+    ];
+    let mut second = entry("second", b"weak");
+    second.record_index = 4;
+    let batch = harness.instruction(
+        &TransacInstruction::CreateBatch {
+            entries: vec![entry(LABEL, PASSWORD), second],
+        },
+        accounts.clone(),
+    );
+    assert_eq!(harness.send(&[batch], &[]).await.result, transac_error(TransacError::WeakPassword));
+    assert_eq!(harness.context.banks_client.get_account(harness.address(LABEL)).await.unwrap(), None);
+
+    let mut second = entry("second", PASSWORD);
+    second.record_index = 4;
+    let batch = harness.instruction(
+        &TransacInstruction::CreateBatch {
+            entries: vec![entry(LABEL, PASSWORD), second],
+        },
+        accounts,
+    );
+    harness.send(&[batch], &[]).await.result.unwrap();
+    assert_eq!(harness.view(LABEL, PASSWORD).await.return_data.unwrap(), SIGNATURE);
+}
+
+#[tokio::test]
+async fn verify_ed25519_needs_the_precompile_before_it() {
+    let mut harness = Harness::created().await;
+    let verify = harness.instruction(
+        &TransacInstruction::VerifyEd25519 {
+            label: LABEL.to_string(),
+            password: PASSWORD.to_vec(),
+            message: b"message".to_vec(),
+            signer: Pubkey::new_unique(),
+        },
+        vec![
+            AccountMeta::new(harness.address(LABEL), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            harness.owner(),
+        ],
+    );
+    assert_eq!(harness.send(&[verify], &[]).await.result, transac_error(TransacError::SignatureInvalid));
+}
+
+#[tokio::test]
+async fn verify_secp256k1_refuses_a_malformed_signature() {
+    let mut harness = Harness::created().await;
+    let verify = TransacInstruction::VerifySecp256k1 {
+        label: LABEL.to_string(),
+        password: PASSWORD.to_vec(),
+        message_hash: [0; 32],
+        expected_address: [0; 20],
+    };
+    assert_eq!(harness.owner_op(&verify).await.result, transac_error(TransacError::MalformedSignature));
+}
+
+#[tokio::test]
+async fn delegates_view_until_revoked() {
+    let mut harness = Harness::created().await;
+    let delegate = Keypair::new();
+    let expires_at = harness.now().await + 3_600;
+    let grant = |expires_at| {
+        Some(DelegateGrant {
+            delegate: delegate.pubkey(),
+            expires_at,
+            access_key: [1; 32],
+        })
+    };
+    let set = |delegate| TransacInstruction::SetDelegate {
+        password: PASSWORD.to_vec(),
+        delegate,
+    };
+    let sent = harness.owner_op(&set(grant(0))).await;
+    assert_eq!(sent.result, program_error(InstructionError::InvalidInstructionData));
+    harness.owner_op(&set(grant(expires_at))).await.result.unwrap();
+
+    let address = harness.address(LABEL);
+    let delegate_view = |nonce| {
+        harness.instruction(
+            &TransacInstruction::DelegateView {
+                label: LABEL.to_string(),
+                access_key: [1; 32],
+                nonce,
+            },
+            vec![AccountMeta::new(address, false), AccountMeta::new_readonly(delegate.pubkey(), true)],
+        )
+    };
+    let (first, second) = (delegate_view(0), delegate_view(1));
+    let sent = harness.send(&[first], &[&delegate]).await;
+    assert_eq!(sent.return_data.unwrap(), SIGNATURE);
+
+    harness.owner_op(&set(None)).await.result.unwrap();
+    let sent = harness.send(&[second], &[&delegate]).await;
+    assert_eq!(sent.result, transac_error(TransacError::NotDelegate));
+}
+
+#[tokio::test]
+async fn set_metadata_refuses_oversized_metadata() {
+    let mut harness = Harness::created().await;
+    let set_metadata = |metadata: Vec<u8>| {
+        harness.instruction(
+            &TransacInstruction::SetMetadata { metadata },
+            vec![
+                AccountMeta::new(harness.address(LABEL), false),
+                harness.owner(),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    };
+    let (too_long, fits) = (set_metadata(vec![1; MAX_METADATA_LEN + 1]), set_metadata(b"note".to_vec()));
+    assert_eq!(harness.send(&[too_long], &[]).await.result, transac_error(TransacError::MetadataTooLong));
+
+    harness.send(&[fits], &[]).await.result.unwrap();
+    let address = harness.address(LABEL);
+    assert_eq!(harness.record(address).await.metadata(), b"note");
+}
+
+#[tokio::test]
+async fn verify_and_call_refuses_a_non_executable_callback() {
+    let mut harness = Harness::created().await;
+    let call = harness.instruction(
+        &TransacInstruction::VerifyAndCall {
+            password: PASSWORD.to_vec(),
+            data: Vec::new(),
+        },
+        vec![
+            AccountMeta::new(harness.address(LABEL), false),
+            AccountMeta::new_readonly(harness.source, false),
+        ],
+    );
+    assert_eq!(harness.send(&[call], &[]).await.result, program_error(InstructionError::IncorrectProgramId));
+}
+
+#[tokio::test]
+async fn rotate_refuses_a_missing_label() {
+    let mut harness = Harness::created().await;
+    let rotate = harness.instruction(
+        &TransacInstruction::RotateSignature {
+            label: "missing".to_string(),
+            password: PASSWORD.to_vec(),
+        },
+        vec![
+            AccountMeta::new_readonly(harness.source, false),
+            AccountMeta::new(harness.address(LABEL), false),
+            harness.owner(),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert_eq!(harness.send(&[rotate], &[]).await.result, transac_error(TransacError::LabelNotFound));
+}
+
+#[tokio::test]
+async fn prehashed_credentials_are_checked() {
+    let mut harness = Harness::created().await;
+    let address = harness.address(LABEL);
+    let verify = harness.instruction(
+        &TransacInstruction::VerifyPrehashed { password_key: [0; 32] },
+        vec![AccountMeta::new(address, false)],
+    );
+    assert_eq!(harness.send(&[verify], &[]).await.return_data.unwrap(), [0]);
+
+    let view = harness.instruction(
+        &TransacInstruction::ViewPrehashed {
+            label: LABEL.to_string(),
+            password_key: [0; 32],
+            nonce: 1,
+        },
+        vec![AccountMeta::new(address, false)],
+    );
+    assert_eq!(harness.send(&[view], &[]).await.result, transac_error(TransacError::StaleNonce));
+}
+
+#[tokio::test]
+async fn create_inline_refuses_an_oversized_signature() {
+    let mut harness = Harness::new().await;
+    let create = |signature: Vec<u8>| {
+        harness.instruction(
+            &TransacInstruction::CreateInline {
+                entropy: [7; SALT_LEN],
+                label: LABEL.to_string(),
+                signature,
+                password: PASSWORD.to_vec(),
+                options: CreateOptions::default(),
+            },
+            vec![
+                AccountMeta::new(harness.address(LABEL), false),
+                harness.owner(),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    };
+    let (too_long, fits) = (create(vec![1; MAX_SIGNATURE_LEN + 1]), create(b"inline".to_vec()));
+    assert_eq!(harness.send(&[too_long], &[]).await.result, transac_error(TransacError::SignatureTooLong));
+
+    harness.send(&[fits], &[]).await.result.unwrap();
+    assert_eq!(harness.view(LABEL, PASSWORD).await.return_data.unwrap(), b"inline");
+}
+
+#[tokio::test]
+async fn remove_refuses_a_missing_label() {
+    let mut harness = Harness::created().await;
+    let remove = |label: &str| TransacInstruction::RemoveSignature {
+        label: label.to_string(),
+        password: PASSWORD.to_vec(),
+    };
+    assert_eq!(harness.owner_op(&remove("missing")).await.result, transac_error(TransacError::LabelNotFound));
+
+    harness.owner_op(&remove(LABEL)).await.result.unwrap();
+    assert_eq!(harness.view(LABEL, PASSWORD).await.result, transac_error(TransacError::LabelNotFound));
+}
+
+#[tokio::test]
+async fn verify_ed25519_checks_the_stored_signature() {
+    let mut harness = Harness::new().await;
+    let signer = Keypair::new();
+    let message = b"pay 10 to alice";
+    let signature = signer.sign_message(message);
+    let create = harness.instruction(
+        &TransacInstruction::CreateInline {
+            entropy: [7; SALT_LEN],
+            label: LABEL.to_string(),
+            signature: signature.as_ref().to_vec(),
+            password: PASSWORD.to_vec(),
+            options: CreateOptions::default(),
+        },
+        vec![
+            AccountMeta::new(harness.address(LABEL), false),
+            harness.owner(),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    harness.send(&[create], &[]).await.result.unwrap();
+
+    let verify = |message: &[u8]| {
+        harness.instruction(
+            &TransacInstruction::VerifyEd25519 {
+                label: LABEL.to_string(),
+                password: PASSWORD.to_vec(),
+                message: message.to_vec(),
+                signer: signer.pubkey(),
+            },
+            vec![
+                AccountMeta::new(harness.address(LABEL), false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                harness.owner(),
+            ],
+        )
+    };
+    let precompile = ed25519_precompile_ix(&signer.pubkey(), message, signature.as_ref());
+    let (good, tampered) = (verify(message), verify(b"pay 99 to alice"));
+    let sent = harness.send(&[precompile.clone(), good], &[]).await;
+    assert_eq!(sent.result, Ok(()));
+    assert!(sent.logs.iter().any(|log| log.contains("Signature verified")));
+
+    // The precompile passes, but over a message other than the one claimed.
+    let sent = harness.send(&[precompile, tampered], &[]).await;
+    let mismatch = InstructionError::Custom(TransacError::SignatureInvalid as u32);
+    assert_eq!(sent.result, Err(TransactionError::InstructionError(2, mismatch)));
+}